@@ -0,0 +1,130 @@
+//! Load time and memory of large levels. Run with `cargo bench --bench load`.
+//!
+//! Memory is counted by a global allocator, as the bytes still allocated by a
+//! loaded level, including the parts of the project kept for saving.
+//!
+//! Storing each layer as arrays of geometry and `FeatureSet`s, rather than
+//! keeping the parsed geometry line and a `Vec<Feature>` per tile, gave:
+//!
+//! | Level   | Load             | Save             | Retained per tile |
+//! |---------|------------------|------------------|-------------------|
+//! | 72x43   | 3.3 ms → 2.1 ms  | 4.0 ms → 1.6 ms  | 491 B → 6.7 B     |
+//! | 200x100 | 24 ms → 19 ms    | 32 ms → 13 ms    | 486 B → 5.3 B     |
+//! | 500x300 | 349 ms → 175 ms  | 252 ms → 127 ms  | 496 B → 5.0 B     |
+//!
+//! Parsing the geometry line straight into the tiles, rather than through
+//! JSON, then brought loading down to 0.33 ms, 1.7 ms and 15 ms. Reading the
+//! binary form kept by the level cache takes 0.04 ms, 0.13 ms and 1.0 ms.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use rainworld_level::{rwlevel::lingo_to_json::TEMPLATE, Feature, RWLevel};
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// A walled room with a mix of poles and path dots inside
+fn project(w: usize, h: usize) -> String {
+    let cell = |x: usize, y: usize| {
+        if x == 0 || y == 0 || x == w - 1 || y == h - 1 {
+            "[1, []]"
+        } else {
+            match (x * 7 + y * 13) % 10 {
+                0 => "[0, [1]]",
+                1 => "[0, [2]]",
+                2 => "[0, [1, 2]]",
+                3 => "[1, [5]]",
+                _ => "[0, []]",
+            }
+        }
+    };
+
+    let columns: Vec<String> = (0..w)
+        .map(|x| {
+            let cells: Vec<String> = (0..h)
+                .map(|y| format!("[{}, [1, []], [0, []]]", cell(x, y)))
+                .collect();
+            format!("[{}]", cells.join(", "))
+        })
+        .collect();
+
+    let size = "#size: point(1, 1), #extraTiles: [0, 0, 0, 0]";
+    assert!(TEMPLATE.contains(size), "Project template has no {size:?}");
+    let rest = TEMPLATE.replace(size, &format!("#size: point({w}, {h}), #extraTiles: [12, 3, 12, 5]"));
+    format!("[{}]\r{}", columns.join(", "), rest.replace('\n', "\r"))
+}
+
+fn time<T>(iterations: u32, mut f: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(f());
+    }
+    start.elapsed() / iterations
+}
+
+fn main() {
+    // Parsing compiles some regexes on first use, which shouldn't count towards the first level
+    RWLevel::from_project_str("WARMUP", &project(4, 4)).expect("Bad benchmark level");
+
+    for (w, h) in [(72, 43), (200, 100), (500, 300)] {
+        let contents = project(w, h);
+
+        let before = ALLOCATED.load(Ordering::Relaxed);
+        let level = RWLevel::from_project_str("BENCH", &contents).expect("Bad benchmark level");
+        let retained = ALLOCATED.load(Ordering::Relaxed) - before;
+
+        let load = time(10, || RWLevel::from_project_str("BENCH", &contents));
+        let scan = time(100, || {
+            let mut poles = 0;
+            for layer in 0..3 {
+                for y in 0..h {
+                    for x in 0..w {
+                        let tile = level.tile(layer, x, y).expect("Tile out of bounds");
+                        poles += tile.features().contains(&Feature::VPole) as usize;
+                    }
+                }
+            }
+            poles
+        });
+        let save = time(10, || level.to_project_string());
+        let bytes = level.to_bytes();
+        let decode = time(10, || RWLevel::from_bytes(&bytes));
+
+        println!(
+            "{w}x{h}: load {:.2} ms, save {:.2} ms, from binary {:.2} ms, scan all tiles {:.3} ms, {:.1} KiB retained ({:.1} bytes per tile)",
+            load.as_secs_f64() * 1000.0,
+            save.as_secs_f64() * 1000.0,
+            decode.as_secs_f64() * 1000.0,
+            scan.as_secs_f64() * 1000.0,
+            retained as f64 / 1024.0,
+            retained as f64 / (w * h * 3) as f64,
+        );
+    }
+}
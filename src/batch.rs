@@ -0,0 +1,258 @@
+//! Loading every project below a directory, such as the whole
+//! `LevelEditorProjects` tree, in parallel.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{cache::LevelCache, RWLevel, Sections};
+
+/// Why a project couldn't be loaded
+#[derive(Debug)]
+pub enum LoadError {
+    /// The file couldn't be read
+    Io(io::Error),
+    /// The file isn't a level editor project, or part of it is malformed
+    NotAProject,
+}
+
+/// Passed to the progress callback after each file is loaded
+#[derive(Clone, Copy, Debug)]
+pub struct Progress<'a> {
+    /// Files loaded so far, including this one
+    pub done: usize,
+    pub total: usize,
+    pub path: &'a Path,
+    pub ok: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    /// Worker threads, or 0 for one per available core
+    pub threads: usize,
+    /// Sections to parse while loading, see [`RWLevel::load_sections`]
+    pub sections: Sections,
+    /// Load through this cache, which always parses whole projects
+    pub cache: Option<LevelCache>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            threads: 0,
+            sections: Sections::ALL,
+            cache: None,
+        }
+    }
+}
+
+/// Every `.txt` file below `dir`, sorted. Unreadable directories are skipped,
+/// and symlinks to directories aren't followed
+pub fn discover<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let mut found = vec![];
+    walk(dir.as_ref(), &mut found);
+    found.sort();
+    found
+}
+
+fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let Ok(kind) = entry.file_type() else {
+            continue
+        };
+
+        // The file type isn't that of a symlink's target, so links to a parent can't loop
+        let path = entry.path();
+        if kind.is_dir() {
+            walk(&path, out);
+        } else if !path.is_dir() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("txt")) {
+            out.push(path);
+        }
+    }
+}
+
+/// Load every project below `dir`, see [`load_files`]
+pub fn load_dir<P: AsRef<Path>>(
+    dir: P,
+    options: &BatchOptions,
+    progress: impl Fn(Progress<'_>) + Sync,
+) -> Vec<(PathBuf, Result<RWLevel, LoadError>)> {
+    load_files(&discover(dir), options, progress)
+}
+
+/// Load the files in parallel, returning a result for each in the same order.
+/// A file which fails doesn't stop the others.
+/// `progress` is called from the worker threads as each file finishes
+pub fn load_files(
+    paths: &[PathBuf],
+    options: &BatchOptions,
+    progress: impl Fn(Progress<'_>) + Sync,
+) -> Vec<(PathBuf, Result<RWLevel, LoadError>)> {
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }.min(paths.len()).max(1);
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, Result<RWLevel, LoadError>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| {
+                let mut loaded = vec![];
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(idx) else {
+                        break loaded
+                    };
+
+                    let result = load(path, options);
+                    progress(Progress {
+                        done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                        total: paths.len(),
+                        path,
+                        ok: result.is_ok(),
+                    });
+                    loaded.push((idx, result));
+                }
+            }))
+            .collect();
+
+        workers.into_iter()
+            .flat_map(|worker| worker.join().expect("Batch worker panicked"))
+            .collect()
+    });
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter()
+        .map(|(idx, result)| (paths[idx].clone(), result))
+        .collect()
+}
+
+fn load(path: &Path, options: &BatchOptions) -> Result<RWLevel, LoadError> {
+    match &options.cache {
+        Some(cache) => cache.load(path),
+        None => {
+            let contents = fs::read_to_string(path).map_err(LoadError::Io)?;
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            RWLevel::from_project_str_sections(&name, &contents, options.sections).ok_or(LoadError::NotAProject)
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::NotAProject => write!(f, "not a readable level editor project"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Mutex};
+
+    use crate::{cache::LevelCache, rwlevel::fixture::{self, Edit}};
+
+    use super::{load_dir, load_files, BatchOptions, LoadError};
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("rwlevel-batch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("SU")).unwrap();
+        fs::write(dir.join("SU/SU_A01.txt"), fixture::project(&["#.#"])).unwrap();
+        fs::write(dir.join("SU/SU_A02.txt"), fixture::project(&["#|.#"])).unwrap();
+        fs::write(dir.join("broken.txt"), "not a project").unwrap();
+        fs::write(dir.join("SU/preview.png"), "").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("SU/loop")).unwrap();
+
+        let seen = Mutex::new(vec![]);
+        let options = BatchOptions { threads: 2, ..Default::default() };
+        let results = load_dir(&dir, &options, |progress| seen.lock().unwrap().push((progress.done, progress.total)));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<_> = results.iter().map(|(path, _)| path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, ["SU_A01.txt", "SU_A02.txt", "broken.txt"]);
+        assert_eq!(results[1].1.as_ref().unwrap().meta().dimensions(), (4, 1));
+        assert!(matches!(results[2].1, Err(LoadError::NotAProject)));
+
+        let mut seen = seen.into_inner().unwrap();
+        seen.sort();
+        assert_eq!(seen, [(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn test_load_malformed() {
+        let dir = std::env::temp_dir().join(format!("rwlevel-batch-malformed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let project = fixture::project(&["#.#"]);
+        let malformed = [
+            project.edit("#effects: []", "#effects: [["),
+            project.edit("#brushSize: 5]", "#brushSize: 5é"),
+            project.edit("point(3, 1)", "point(-3, 1)"),
+            project.edit("point(3, 1)", "point(100000, 100000)"),
+        ];
+        for (idx, contents) in malformed.iter().enumerate() {
+            fs::write(dir.join(format!("{idx}.txt")), contents).unwrap();
+        }
+
+        let results = load_dir(&dir, &BatchOptions::default(), |_| {});
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results.len(), malformed.len());
+        for (path, result) in results {
+            assert!(matches!(result, Err(LoadError::NotAProject)), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_load_nothing() {
+        let dir = std::env::temp_dir().join(format!("rwlevel-batch-empty-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let called = Mutex::new(false);
+        assert!(load_dir(&dir, &BatchOptions::default(), |_| *called.lock().unwrap() = true).is_empty());
+        assert!(load_dir(dir.join("missing"), &BatchOptions::default(), |_| *called.lock().unwrap() = true).is_empty());
+        assert!(load_files(&[], &BatchOptions { threads: 4, ..Default::default() }, |_| *called.lock().unwrap() = true).is_empty());
+        assert!(!called.into_inner().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_files() {
+        let dir = std::env::temp_dir().join(format!("rwlevel-batch-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("SU_A01.txt"), fixture::project(&["#.#"])).unwrap();
+        fs::write(dir.join("SU_A02.txt"), fixture::project(&["#..#"])).unwrap();
+
+        // Listed files keep their order, missing ones are Io errors, and there
+        // can be more threads than files
+        let paths = [dir.join("SU_A02.txt"), dir.join("missing.txt"), dir.join("SU_A01.txt")];
+        let options = BatchOptions { threads: 8, ..Default::default() };
+        let results = load_files(&paths, &options, |_| {});
+        assert_eq!(results.iter().map(|(path, _)| path).collect::<Vec<_>>(), paths.iter().collect::<Vec<_>>());
+        assert_eq!(results[0].1.as_ref().unwrap().meta().dimensions(), (4, 1));
+        assert!(matches!(results[1].1, Err(LoadError::Io(_))));
+        assert_eq!(results[2].1.as_ref().unwrap().meta().dimensions(), (3, 1));
+
+        // Through a cache, which fails the same way
+        let options = BatchOptions { threads: 1, cache: Some(LevelCache::new(dir.join("cache"))), ..Default::default() };
+        for _ in 0..2 {
+            let cached = load_files(&paths, &options, |_| {});
+            assert_eq!(cached[0].1.as_ref().unwrap().to_project_string(), results[0].1.as_ref().unwrap().to_project_string());
+            assert!(matches!(cached[1].1, Err(LoadError::Io(_))));
+        }
+        assert_eq!(fs::read_dir(dir.join("cache")).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
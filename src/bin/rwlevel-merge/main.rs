@@ -0,0 +1,74 @@
+use std::{path::Path, process::{Command, ExitCode}};
+
+use rainworld_level::{merge::merge, RWLevel};
+
+const USAGE: &str = "\
+Usage: rwlevel-merge <base> <ours> <theirs> [<path>]
+
+Three-way merge of level editor projects, writing the result over <ours>.
+Tiles, effects, props, cameras and settings are merged one by one, and
+conflicts keep our side. Files which aren't level projects are merged
+as text with `git merge-file`.
+
+To use it as a git merge driver, add to .gitattributes:
+
+    *.txt merge=rwlevel
+
+and to .git/config:
+
+    [merge \"rwlevel\"]
+        name = Rain World level merge
+        driver = rwlevel-merge %O %A %B %P
+
+Exit codes: 0 on a clean merge, 1 on conflicts, 2 on errors.";
+
+fn main() -> ExitCode {
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    let (base, ours, theirs, path) = match argv.as_slice() {
+        [base, ours, theirs] => (base, ours, theirs, ours),
+        [base, ours, theirs, path] => (base, ours, theirs, path),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2)
+        }
+    };
+
+    let levels = [base, ours, theirs].map(|file| RWLevel::load(Path::new(file)));
+    let [Some(base_level), Some(our_level), Some(their_level)] = levels else {
+        return merge_text(base, ours, theirs)
+    };
+
+    let Some(merged) = merge(&base_level, &our_level, &their_level) else {
+        eprintln!("error: {path}: the merged level can't be read back");
+        return ExitCode::from(2)
+    };
+    if let Err(e) = merged.level().save(ours) {
+        eprintln!("error: {ours}: {e}");
+        return ExitCode::from(2)
+    }
+
+    for conflict in merged.conflicts() {
+        eprintln!("{path}: conflict: {conflict}");
+    }
+
+    match merged.is_clean() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::from(1),
+    }
+}
+
+/// Fall back to git's own line-based merge
+fn merge_text(base: &str, ours: &str, theirs: &str) -> ExitCode {
+    let status = Command::new("git")
+        .args(["merge-file", "-L", "ours", "-L", "base", "-L", "theirs", ours, base, theirs])
+        .status();
+
+    match status {
+        Ok(status) if status.success() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: couldn't run git merge-file: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
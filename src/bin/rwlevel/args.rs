@@ -0,0 +1,62 @@
+use std::collections::{HashMap, HashSet};
+
+/// Arguments of a single subcommand, split into positionals, options which
+/// take a value (`--format json` or `--format=json`) and bare flags (`--fix`)
+pub struct Args {
+    pub positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl Args {
+    /// `with_value` lists the options which take a value, `flags` those which don't.
+    /// Anything else starting with `-` is an error
+    pub fn parse(argv: &[String], with_value: &[&str], flags: &[&str]) -> Result<Self, String> {
+        let mut args = Args {
+            positional: vec![],
+            options: HashMap::new(),
+            flags: HashSet::new(),
+        };
+
+        let mut iter = argv.iter();
+        while let Some(arg) = iter.next() {
+            if !arg.starts_with('-') || arg == "-" {
+                args.positional.push(arg.clone());
+                continue;
+            }
+
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (arg.as_str(), None),
+            };
+
+            if with_value.contains(&name) {
+                let value = inline.or_else(|| iter.next().cloned())
+                    .ok_or_else(|| format!("{name} needs a value"))?;
+                args.options.insert(name.to_owned(), value);
+            } else if flags.contains(&name) && inline.is_none() {
+                args.flags.insert(name.to_owned());
+            } else {
+                return Err(format!("unknown option {arg}"))
+            }
+        }
+
+        Ok(args)
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    /// Parse an option's value, falling back to `default` if it wasn't given
+    pub fn parsed<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        match self.option(name) {
+            Some(value) => value.parse().map_err(|_| format!("bad value for {name}: {value}")),
+            None => Ok(default),
+        }
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+}
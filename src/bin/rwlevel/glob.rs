@@ -0,0 +1,92 @@
+//! Just enough globbing for shells which don't expand patterns themselves.
+//! Supports `*` and `?` within a path component, and `**` for any number of directories.
+
+use std::{fs::{read_dir, symlink_metadata}, path::{Path, PathBuf}};
+
+/// Paths matching the pattern, sorted. Arguments without wildcards are
+/// returned as-is, so missing files are still reported when loaded
+pub fn expand(pattern: &str) -> Vec<PathBuf> {
+    if !pattern.contains(['*', '?']) {
+        return vec![PathBuf::from(pattern)];
+    }
+
+    let components: Vec<&str> = pattern.split(['/', '\\']).collect();
+    let literal = components.iter().take_while(|c| !c.contains(['*', '?'])).count();
+
+    let base = match components[..literal].join("/") {
+        root if root.is_empty() && pattern.starts_with('/') => PathBuf::from("/"),
+        root if root.is_empty() => PathBuf::from("."),
+        root => PathBuf::from(root),
+    };
+
+    let mut matches = vec![];
+    walk(&base, &components[literal..], literal == 0 && !pattern.starts_with('/'), &mut matches);
+    matches.sort();
+    matches
+}
+
+/// Every `.txt` file below the directory, or the path itself if it's a file
+pub fn projects_in(path: &Path) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+
+    rainworld_level::batch::discover(path)
+}
+
+/// `relative` strips the leading `./` added when the pattern had no literal prefix
+fn walk(dir: &Path, components: &[&str], relative: bool, out: &mut Vec<PathBuf>) {
+    let Some((first, rest)) = components.split_first() else {
+        out.push(dir.to_path_buf());
+        return
+    };
+
+    if *first == "**" {
+        // Zero directories, then one or more. Symlinks aren't followed, in case they loop
+        walk(dir, rest, relative, out);
+        let is_dir = |p: &PathBuf| symlink_metadata(p).is_ok_and(|meta| meta.is_dir());
+        for sub in children(dir, relative).into_iter().filter(is_dir) {
+            walk(&sub, components, false, out);
+        }
+        return
+    }
+
+    for child in children(dir, relative) {
+        let name = child.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        if !wildcard(first.as_bytes(), name.as_bytes()) {
+            continue;
+        }
+
+        if rest.is_empty() {
+            if child.is_file() {
+                out.push(child);
+            }
+        } else if child.is_dir() {
+            walk(&child, rest, false, out);
+        }
+    }
+}
+
+fn children(dir: &Path, relative: bool) -> Vec<PathBuf> {
+    let Ok(entries) = read_dir(dir) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match relative {
+            true => PathBuf::from(entry.file_name()),
+            false => entry.path(),
+        })
+        .collect()
+}
+
+fn wildcard(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => wildcard(rest, name) || (!name.is_empty() && wildcard(pattern, &name[1..])),
+        (Some((b'?', rest)), Some((_, name_rest))) => wildcard(rest, name_rest),
+        (Some((p, rest)), Some((n, name_rest))) => p.eq_ignore_ascii_case(n) && wildcard(rest, name_rest),
+        _ => false,
+    }
+}
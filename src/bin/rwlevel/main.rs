@@ -0,0 +1,527 @@
+mod args;
+mod glob;
+
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, process::ExitCode};
+
+use args::Args;
+use rainworld_level::{
+    batch::{self, BatchOptions},
+    cache::LevelCache,
+    compiled::{stale_check_dir, StaleCheck},
+    diff,
+    patch::Patch,
+    lint::{report, LintConfig, Linter, Severity},
+    movement::MovementProfile,
+    render, Feature, RWLevel, Section, Sections,
+};
+
+const USAGE: &str = "\
+Usage: rwlevel <command> [options] <files...>
+
+Files may be paths or glob patterns (`*`, `?`, `**`).
+
+Commands:
+  info <files...>                      Summarize each level
+  lint [options] <files...>            Check levels for common mistakes
+      --format <text|json|sarif>       Output format (default text)
+      --config <file>                  Rule configuration file
+      --fix                            Apply fixes and save before checking
+  render [options] <file>              Preview the geometry of a level
+      --layer <1-3>                    Layer to print as text (default 1)
+      --ppm <out.ppm>                  Write an image of all layers instead
+      --scale <pixels>                 Pixels per tile of the image (default 8)
+  convert [--raw] <input> <output>     Convert between projects (.txt) and the JSON schema (.json)
+      --raw                            Write the editor's own fields as JSON instead
+  diff [--format <text|json|patch>] <old> <new>
+                                       List the changes between two levels
+  patch [-o <out>] <file> <patch>      Apply a patch written by `diff --format patch`
+  resize [-o <out>] <file> <w> <h>     Resize a level, keeping the top-left corner
+  canonicalize [--check] <files...>    Reset the editor's UI state saved in projects
+      --check                          Only list the projects which aren't canonical
+  fingerprint <files...>               Print a hash of each level's content
+  stats <files...>                     Count geometry, features and shortcuts
+  reach [--creature <name>] <files...> List the exits and dens a creature can get between
+      --creature <name>                slugcat, lizard, scavenger or batfly (default slugcat)
+  stale-check <projects> <rooms>       Find projects which differ from their rendered rooms
+  batch <command> [options] <dirs...>  Run a command over every project below directories
+
+Set RWLEVEL_CACHE to a directory to cache parsed levels between runs.
+
+Exit codes: 0 on success, 1 if problems were found, 2 on errors.";
+
+/// Result of a command, ordered by how bad it is
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Ok,
+    /// Lint errors, differences, stale rooms
+    Findings,
+    /// Bad usage, or a file couldn't be read or written
+    Failed,
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+fn main() -> ExitCode {
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = argv.split_first() else {
+        eprintln!("{USAGE}");
+        return Status::Failed.into()
+    };
+
+    match run(command, rest, false) {
+        Ok(status) => status.into(),
+        Err(msg) => {
+            eprintln!("error: {msg}");
+            eprintln!("Run `rwlevel help` for usage.");
+            Status::Failed.into()
+        }
+    }
+}
+
+/// Dispatch a subcommand. `recursive` makes directory arguments expand to every project below them
+fn run(command: &str, argv: &[String], recursive: bool) -> Result<Status, String> {
+    match command {
+        "info" => info(&Args::parse(argv, &[], &[])?, recursive),
+        "lint" => lint(&Args::parse(argv, &["--format", "--config"], &["--fix"])?, recursive),
+        "render" => render(&Args::parse(argv, &["--layer", "--ppm", "--scale"], &[])?),
+        "convert" => convert(&Args::parse(argv, &[], &["--raw"])?),
+        "diff" => diff(&Args::parse(argv, &["--format"], &[])?),
+        "patch" => patch(&Args::parse(argv, &["-o"], &[])?),
+        "resize" => resize(&Args::parse(argv, &["-o"], &[])?),
+        "canonicalize" => canonicalize(&Args::parse(argv, &[], &["--check"])?, recursive),
+        "fingerprint" => fingerprint(&Args::parse(argv, &[], &[])?, recursive),
+        "stats" => stats(&Args::parse(argv, &[], &[])?, recursive),
+        "reach" => reach(&Args::parse(argv, &["--creature"], &[])?, recursive),
+        "stale-check" => stale(&Args::parse(argv, &[], &[])?),
+        "batch" => batch(argv),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(Status::Ok)
+        }
+        other => Err(format!("unknown command `{other}`")),
+    }
+}
+
+/// Expand globs (and directories, if `recursive`) into the list of files to work on
+fn files(patterns: &[String], recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let files: Vec<PathBuf> = patterns.iter()
+        .flat_map(|pattern| glob::expand(pattern))
+        .flat_map(|path| match recursive {
+            true => glob::projects_in(&path),
+            false => vec![path],
+        })
+        .collect();
+
+    if files.is_empty() {
+        return Err("no files given or matched".into())
+    }
+
+    Ok(files)
+}
+
+fn load(path: &Path) -> Option<RWLevel> {
+    load_sections(path, Sections::ALL)
+}
+
+/// Load only what a command needs, leaving the rest of the file until it's used
+fn load_sections(path: &Path, sections: Sections) -> Option<RWLevel> {
+    let level = RWLevel::load_sections(path, sections);
+    if level.is_none() {
+        eprintln!("error: {}: not a readable level editor project", path.display());
+    }
+
+    level
+}
+
+/// Files loaded in parallel at a time, bounding how many levels are held in memory
+const LOAD_CHUNK: usize = 64;
+
+/// Run `f` over every file which loads, in order, failing if any didn't
+fn for_each_level(files: &[PathBuf], mut f: impl FnMut(&Path, RWLevel) -> Status) -> Status {
+    let options = BatchOptions {
+        cache: std::env::var_os("RWLEVEL_CACHE").map(LevelCache::new),
+        ..Default::default()
+    };
+
+    files.chunks(LOAD_CHUNK)
+        .flat_map(|chunk| batch::load_files(chunk, &options, |_| {}))
+        .map(|(path, level)| match level {
+            Ok(level) => f(&path, level),
+            Err(err) => {
+                eprintln!("error: {}: {err}", path.display());
+                Status::Failed
+            }
+        })
+        .max()
+        .unwrap_or(Status::Ok)
+}
+
+fn exactly<const N: usize>(args: &Args, what: &str) -> Result<[String; N], String> {
+    args.positional.clone()
+        .try_into()
+        .map_err(|_| format!("expected {what}"))
+}
+
+fn info(args: &Args, recursive: bool) -> Result<Status, String> {
+    let files = files(&args.positional, recursive)?;
+
+    Ok(for_each_level(&files, |path, level| {
+        let meta = level.meta();
+        let (w, h) = meta.dimensions();
+        let (left, top, right, bottom) = meta.extra_tiles();
+        let nodes = level.room_nodes();
+        let effects: Vec<&str> = level.effects().iter().map(|e| e.name()).collect();
+
+        println!("{} ({})", level.name(), path.display());
+        println!("  size: {w}x{h}, border {left} {top} {right} {bottom}");
+        match meta.water_level() {
+            -1 => println!("  water: none"),
+            water => println!("  water: level {water}, {}", if meta.water_in_front() { "in front" } else { "behind" }),
+        }
+        println!("  cameras: {}", meta.cameras().len());
+        println!("  effects: {} [{}]", effects.len(), effects.join(", "));
+        println!("  exits: {}, dens: {}", nodes.exits.len(), nodes.dens.len());
+
+        Status::Ok
+    }))
+}
+
+fn lint(args: &Args, recursive: bool) -> Result<Status, String> {
+    let files = files(&args.positional, recursive)?;
+    let format = args.option("--format").unwrap_or("text");
+    if !["text", "json", "sarif"].contains(&format) {
+        return Err(format!("unknown format `{format}`"))
+    }
+
+    let mut linter = Linter::new();
+    if let Some(config) = args.option("--config") {
+        linter.set_config(LintConfig::load(config)?);
+
+        let mut unknown: Vec<&str> = linter.unknown_config_rules().collect();
+        unknown.sort();
+        for name in unknown {
+            eprintln!("warning: {config}: no rule is called `{name}`");
+        }
+    }
+
+    let mut status = Status::Ok;
+    let mut results = vec![];
+    for path in files {
+        let Some(mut level) = load(&path) else {
+            status = Status::Failed;
+            continue;
+        };
+
+        if args.flag("--fix") {
+            let fixes = linter.fix(&mut level);
+            if !fixes.is_empty() {
+                if let Err(e) = level.save(&path) {
+                    eprintln!("error: {}: {e}", path.display());
+                    status = Status::Failed;
+                }
+            }
+            for fix in fixes {
+                eprintln!("{}: {fix}", path.display());
+            }
+        }
+
+        let diagnostics = linter.check(&level);
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            status = status.max(Status::Findings);
+        }
+        results.push((path, diagnostics));
+    }
+
+    match format {
+        "json" => println!("{:#}", report::to_json(&results)),
+        "sarif" => println!("{:#}", report::to_sarif(&linter, &results)),
+        _ => {
+            for (path, diagnostics) in &results {
+                for diagnostic in diagnostics {
+                    println!("{}: {diagnostic}", path.display());
+                }
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+fn render(args: &Args) -> Result<Status, String> {
+    let [file] = exactly(args, "one file")?;
+    let Some(level) = load_sections(Path::new(&file), Section::Geometry.into()) else {
+        return Ok(Status::Failed)
+    };
+
+    if let Some(out) = args.option("--ppm") {
+        let scale = args.parsed("--scale", 8)?;
+        fs::write(out, render::ppm(&level, scale)).map_err(|e| format!("{out}: {e}"))?;
+        return Ok(Status::Ok)
+    }
+
+    let layer: usize = args.parsed("--layer", 1)?;
+    if !(1..=3).contains(&layer) {
+        return Err("--layer must be 1, 2 or 3".into())
+    }
+
+    print!("{}", render::ascii(&level, layer - 1));
+    Ok(Status::Ok)
+}
+
+fn convert(args: &Args) -> Result<Status, String> {
+    let [input, output] = exactly(args, "an input and an output file")?;
+    let level = match extension(&input) {
+        Some("json") => {
+            let contents = fs::read_to_string(&input).map_err(|e| format!("{input}: {e}"))?;
+            let value = serde_json::from_str(&contents).map_err(|e| format!("{input}: {e}"))?;
+            RWLevel::import_json(&value).map_err(|e| format!("{input}: {e}"))?
+        }
+        _ => match load(Path::new(&input)) {
+            Some(level) => level,
+            None => return Ok(Status::Failed),
+        },
+    };
+
+    let written = match extension(&output) {
+        Some("json") if args.flag("--raw") => fs::write(&output, format!("{:#}", level.to_raw_json())),
+        Some("json") => fs::write(&output, format!("{:#}", level.export_json())),
+        Some("txt") => level.save(&output),
+        _ => return Err(format!("{output}: can only convert to .json or .txt")),
+    };
+
+    written.map_err(|e| format!("{output}: {e}"))?;
+    Ok(Status::Ok)
+}
+
+fn extension(path: &str) -> Option<&str> {
+    Path::new(path).extension().and_then(|ext| ext.to_str())
+}
+
+fn diff(args: &Args) -> Result<Status, String> {
+    let [old, new] = exactly(args, "two files")?;
+    let (Some(old), Some(new)) = (load(Path::new(&old)), load(Path::new(&new))) else {
+        return Ok(Status::Failed)
+    };
+
+    let changes = diff::diff(&old, &new);
+    match args.option("--format").unwrap_or("text") {
+        "text" if changes.is_empty() => {}
+        "text" => println!("{changes}"),
+        "json" => println!("{:#}", changes.to_json()),
+        "patch" => println!("{:#}", Patch::from(changes.clone()).to_json()),
+        other => return Err(format!("unknown format `{other}`")),
+    }
+
+    Ok(if changes.is_empty() { Status::Ok } else { Status::Findings })
+}
+
+fn patch(args: &Args) -> Result<Status, String> {
+    let [file, patch] = exactly(args, "a file and a patch")?;
+    let contents = fs::read_to_string(&patch).map_err(|e| format!("{patch}: {e}"))?;
+    let value = serde_json::from_str(&contents).map_err(|e| format!("{patch}: {e}"))?;
+    let patch = Patch::from_json(&value).map_err(|e| format!("{patch}: {e}"))?;
+
+    let Some(mut level) = load(Path::new(&file)) else {
+        return Ok(Status::Failed)
+    };
+
+    if let Err(conflicts) = level.apply_patch(&patch) {
+        for conflict in &conflicts {
+            println!("conflict: {conflict}");
+        }
+        return Ok(Status::Findings)
+    }
+
+    let out = args.option("-o").unwrap_or(&file);
+    level.save(out).map_err(|e| format!("{out}: {e}"))?;
+    Ok(Status::Ok)
+}
+
+fn resize(args: &Args) -> Result<Status, String> {
+    let [file, w, h] = exactly(args, "a file, a width and a height")?;
+    let w: usize = w.parse().map_err(|_| format!("bad width {w}"))?;
+    let h: usize = h.parse().map_err(|_| format!("bad height {h}"))?;
+    if w == 0 || h == 0 {
+        return Err("levels must be at least 1x1".into())
+    }
+
+    let Some(mut level) = load(Path::new(&file)) else {
+        return Ok(Status::Failed)
+    };
+
+    level.resize(w, h);
+    let out = args.option("-o").unwrap_or(&file);
+    level.save(out).map_err(|e| format!("{out}: {e}"))?;
+    Ok(Status::Ok)
+}
+
+fn canonicalize(args: &Args, recursive: bool) -> Result<Status, String> {
+    let files = files(&args.positional, recursive)?;
+    let check = args.flag("--check");
+
+    Ok(for_each_level(&files, |path, level| {
+        if level.is_canonical() {
+            return Status::Ok
+        }
+        if check {
+            println!("{}: not canonical", path.display());
+            return Status::Findings
+        }
+
+        // Saving resets the UI state
+        match level.save(path) {
+            Ok(()) => Status::Ok,
+            Err(e) => {
+                eprintln!("error: {}: {e}", path.display());
+                Status::Failed
+            }
+        }
+    }))
+}
+
+fn fingerprint(args: &Args, recursive: bool) -> Result<Status, String> {
+    let files = files(&args.positional, recursive)?;
+
+    Ok(for_each_level(&files, |path, level| {
+        println!("{}  {}", level.fingerprint(), path.display());
+        Status::Ok
+    }))
+}
+
+fn stats(args: &Args, recursive: bool) -> Result<Status, String> {
+    let files = files(&args.positional, recursive)?;
+
+    Ok(for_each_level(&files, |_, level| {
+        println!("{}", level.name());
+
+        for layer in 0..3 {
+            let mut geometry = BTreeMap::new();
+            let mut features: BTreeMap<String, usize> = BTreeMap::new();
+            for (_, _, tile) in level.tiles(layer) {
+                *geometry.entry(format!("{:?}", tile.geometry)).or_insert(0) += 1;
+                for feature in tile.features() {
+                    *features.entry(format!("{feature:?}")).or_default() += 1;
+                }
+            }
+
+            println!("  L{} geometry: {}", layer + 1, counts(&geometry));
+            if !features.is_empty() {
+                println!("  L{} features: {}", layer + 1, counts(&features));
+            }
+        }
+
+        let network = level.shortcuts();
+        let wormgrass = level.tiles_with_feature(Feature::WormGrass)
+            .filter(|&(layer, ..)| layer == RWLevel::L1_FG)
+            .count();
+        println!("  shortcuts: {} entrances, {} connections, {} broken",
+            network.shortcuts.len(), network.connections().count(), network.broken().count());
+        println!("  wormgrass tiles: {wormgrass}");
+        println!("  effects: {}", level.effects().len());
+
+        Status::Ok
+    }))
+}
+
+fn reach(args: &Args, recursive: bool) -> Result<Status, String> {
+    let creature = args.option("--creature").unwrap_or("slugcat");
+    let profile = MovementProfile::named(creature).ok_or_else(|| format!("unknown creature `{creature}`"))?;
+    let files = files(&args.positional, recursive)?;
+
+    Ok(for_each_level(&files, |_, level| {
+        let reach = level.reachability(&profile);
+        let access = level.accessibility(&profile);
+        let exits = level.room_nodes().exits.len();
+        let describe = |index: usize| match index < exits {
+            true => format!("exit {index}"),
+            false => format!("den {index}"),
+        };
+        println!("{}", level.name());
+
+        for (node, reachable) in reach.nodes.iter().zip(&reach.reachable) {
+            let others: Vec<String> = reachable.iter()
+                .filter(|&&other| other != node.index)
+                .map(|&other| describe(other))
+                .collect();
+            let (x, y) = node.entrance;
+            println!("  {} ({x}, {y}): {}", describe(node.index), match others.is_empty() {
+                true => "nothing else".to_owned(),
+                false => others.join(", "),
+            });
+        }
+
+        for &(exit, (x, y)) in &reach.stuck {
+            println!("  entering from exit {exit}, the {creature} can get stuck at ({x}, {y})");
+        }
+        for &(x, y) in &access.stranded {
+            println!("  the {creature} can't get out of the room from ({x}, {y})");
+        }
+
+        match reach.stuck.is_empty() && access.stranded.is_empty() {
+            true => Status::Ok,
+            false => Status::Findings,
+        }
+    }))
+}
+
+fn counts(map: &BTreeMap<String, usize>) -> String {
+    map.iter()
+        .map(|(name, count)| format!("{name} {count}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn stale(args: &Args) -> Result<Status, String> {
+    let [projects, rooms] = exactly(args, "a project directory and a rooms directory")?;
+
+    let mut status = Status::Ok;
+    for (path, check) in stale_check_dir(&projects, &rooms) {
+        match check {
+            StaleCheck::Checked(report) => {
+                if report.is_stale() {
+                    status = status.max(Status::Findings);
+                }
+                println!("{report}");
+            }
+            StaleCheck::NotRendered => {
+                status = status.max(Status::Findings);
+                println!("{}: never rendered", path.display());
+            }
+            StaleCheck::ProjectUnreadable => {
+                status = Status::Failed;
+                eprintln!("error: {}: not a readable level editor project", path.display());
+            }
+            StaleCheck::RoomUnreadable(room) => {
+                status = Status::Failed;
+                eprintln!("error: {}: not a readable rendered room", room.display());
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+/// Same as running the command directly, except directories are searched
+/// for projects and a summary is printed at the end
+fn batch(argv: &[String]) -> Result<Status, String> {
+    let Some((command, rest)) = argv.split_first() else {
+        return Err("batch needs a command".into())
+    };
+
+    if !["info", "lint", "stats", "reach", "canonicalize", "fingerprint"].contains(&command.as_str()) {
+        return Err(format!("`{command}` can't be run in batch"))
+    }
+
+    let status = run(command, rest, true)?;
+    eprintln!("batch {command}: {}", match status {
+        Status::Ok => "ok",
+        Status::Findings => "problems found",
+        Status::Failed => "some files failed",
+    });
+
+    Ok(status)
+}
@@ -0,0 +1,258 @@
+//! An on-disk cache of parsed levels in their binary form, see
+//! [`RWLevel::to_bytes`]. Entries are keyed by the project's path, and reused
+//! while the project's modification time and size are unchanged, or, if those
+//! changed, while its contents hash the same.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::UNIX_EPOCH,
+};
+
+use crate::{batch::LoadError, fingerprint::Fnv, RWLevel};
+
+const MAGIC: &[u8; 4] = b"RWLC";
+/// Magic, level format version, mtime seconds and nanoseconds, size and hash
+const HEADER_LEN: usize = 4 + 4 + 8 + 4 + 8 + 8;
+
+/// A directory of cached levels, safe to share between threads and processes
+#[derive(Clone, Debug)]
+pub struct LevelCache {
+    dir: PathBuf,
+}
+
+/// What's known about the project an entry was made from
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Source {
+    mtime: (u64, u32),
+    size: u64,
+}
+
+impl LevelCache {
+    /// The directory is created when the first entry is written
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Load a project, from the cache if it has an up to date entry. Otherwise
+    /// the project is parsed and the entry written, failing to write it isn't
+    /// an error
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<RWLevel, LoadError> {
+        let path = path.as_ref();
+        let source = Source::of(path).map_err(LoadError::Io)?;
+        let entry = self.entry_path(path);
+        let cached = fs::read(&entry).ok();
+        let made_from = cached.as_deref().and_then(read_header);
+
+        if made_from.is_some_and(|(from, _)| from == source) {
+            if let Some(level) = cached.as_deref().and_then(read_payload) {
+                return Ok(level);
+            }
+        }
+
+        let contents = fs::read_to_string(path).map_err(LoadError::Io)?;
+        let hash = hash(&contents);
+
+        // Touched but not changed, so only the header is out of date
+        if made_from.is_some_and(|(_, from)| from == hash) {
+            if let Some(level) = cached.as_deref().and_then(read_payload) {
+                let mut bytes = cached.expect("Entry was read");
+                bytes[..HEADER_LEN].copy_from_slice(&header(source, hash));
+                let _ = write_atomic(&entry, &bytes);
+                return Ok(level);
+            }
+        }
+
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let level = RWLevel::from_project_str(&name, &contents).ok_or(LoadError::NotAProject)?;
+
+        let mut bytes = header(source, hash).to_vec();
+        bytes.extend(level.to_bytes());
+        let _ = fs::create_dir_all(&self.dir).and_then(|_| write_atomic(&entry, &bytes));
+
+        Ok(level)
+    }
+
+    /// Remove every entry, leaving the directory
+    pub fn clear(&self) -> io::Result<()> {
+        let entries = match fs::read_dir(&self.dir) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            entries => entries?,
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "rwlc") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, path: &Path) -> PathBuf {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut key = Fnv(Fnv::OFFSET);
+        key.write(path.as_os_str().as_encoded_bytes());
+        self.dir.join(format!("{:016x}.rwlc", key.0))
+    }
+}
+
+impl Source {
+    fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata.modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos()));
+
+        Ok(Self { mtime, size: metadata.len() })
+    }
+}
+
+fn hash(contents: &str) -> u64 {
+    let mut hash = Fnv(Fnv::OFFSET);
+    hash.write(contents.as_bytes());
+    hash.0
+}
+
+fn header(source: Source, hash: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    let fields: [&[u8]; 6] = [
+        MAGIC,
+        &RWLevel::BINARY_VERSION.to_le_bytes(),
+        &source.mtime.0.to_le_bytes(),
+        &source.mtime.1.to_le_bytes(),
+        &source.size.to_le_bytes(),
+        &hash.to_le_bytes(),
+    ];
+
+    let mut offset = 0;
+    for field in fields {
+        header[offset..offset + field.len()].copy_from_slice(field);
+        offset += field.len();
+    }
+    header
+}
+
+/// The source and hash an entry was made from, without reading its level
+fn read_header(bytes: &[u8]) -> Option<(Source, u64)> {
+    let header = bytes.get(..HEADER_LEN)?;
+    let u64_at = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+    let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+
+    if &header[..4] != MAGIC || u32_at(4) != RWLevel::BINARY_VERSION {
+        return None
+    }
+
+    let source = Source {
+        mtime: (u64_at(8), u32_at(16)),
+        size: u64_at(20),
+    };
+    Some((source, u64_at(28)))
+}
+
+fn read_payload(bytes: &[u8]) -> Option<RWLevel> {
+    RWLevel::from_bytes(bytes.get(HEADER_LEN..)?)
+}
+
+/// Write through a temporary file, so other readers never see half an entry
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+    let tmp = path.with_extension(format!("{}-{}.tmp", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::{rwlevel::fixture, RWLevel};
+
+    use super::{header, read_header, LevelCache, Source};
+
+    #[test]
+    fn test_binary_round_trip() {
+        let level = RWLevel::from_project_str("SU_A01", &fixture::project(&["#.|#", "/-=\\"])).unwrap();
+        let bytes = level.to_bytes();
+        let decoded = RWLevel::from_bytes(&bytes).unwrap();
+
+        assert_eq!(decoded.name(), "SU_A01");
+        assert_eq!(decoded.to_project_string(), level.to_project_string());
+        assert!(RWLevel::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+
+        // A corrupt length is rejected rather than allocated
+        let name = 4 + 4 + 4 + "SU_A01".len();
+        let mut corrupt = bytes.clone();
+        corrupt[name + 1..name + 5].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(RWLevel::from_bytes(&corrupt).is_none());
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("rwlevel-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("SU_A01.txt");
+        fs::write(&project, fixture::project(&["#.#"])).unwrap();
+
+        let cache = LevelCache::new(dir.join("cache"));
+        let level = cache.load(&project).unwrap();
+        let entries = || fs::read_dir(cache.dir()).unwrap().count();
+        assert_eq!(entries(), 1);
+        assert_eq!(cache.load(&project).unwrap().to_project_string(), level.to_project_string());
+
+        // A changed project replaces its entry
+        fs::write(&project, fixture::project(&["#..#"])).unwrap();
+        assert_eq!(cache.load(&project).unwrap().meta().dimensions(), (4, 1));
+        assert_eq!(entries(), 1);
+
+        cache.clear().unwrap();
+        assert_eq!(entries(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_touched_project() {
+        let dir = std::env::temp_dir().join(format!("rwlevel-cache-touched-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("SU_A01.txt");
+        let contents = fixture::project(&["#.#"]);
+        fs::write(&project, &contents).unwrap();
+
+        // An entry holding another level, but made from these contents
+        let cache = LevelCache::new(dir.join("cache"));
+        cache.load(&project).unwrap();
+        let entry = cache.entry_path(&project);
+        let (made_from, hash) = read_header(&fs::read(&entry).unwrap()).unwrap();
+        let mut bytes = header(made_from, hash).to_vec();
+        bytes.extend(fixture::level(&["#..#"]).to_bytes());
+        fs::write(&entry, bytes).unwrap();
+
+        // Touching the project changes its mtime but not its hash, so the
+        // entry is still used and its header brought up to date
+        let file = fs::File::options().write(true).open(&project).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        drop(file);
+        assert_eq!(cache.load(&project).unwrap().meta().dimensions(), (4, 1));
+
+        let bytes = fs::read(&entry).unwrap();
+        let (source, rehashed) = read_header(&bytes).unwrap();
+        assert!(source == Source::of(&project).unwrap() && source != made_from);
+        assert_eq!(rehashed, hash);
+
+        // A real change is parsed again
+        fs::write(&project, fixture::project(&["#.|.#"])).unwrap();
+        assert_eq!(cache.load(&project).unwrap().meta().dimensions(), (5, 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,485 @@
+//! Rooms as written by the level editor's renderer (`world/xx-rooms/xx_room.txt`),
+//! and detection of projects that were edited without being re-rendered.
+
+use std::{fmt, fs::{read_dir, read_to_string}, path::{Path, PathBuf}};
+
+use crate::{Feature, Geometry, Point, RWLevel};
+
+/// Height of a camera in pixels, used to flip editor camera positions into game space
+const CAMERA_HEIGHT_PX: isize = 800;
+/// Pixels per tile in both the editor and the game
+const TILE_PX: isize = 20;
+/// The renderer writes the collision geometry on the 12th line of the file
+const GEOMETRY_LINE: usize = 11;
+
+/// Collision terrain used by the game. Coarser than the editor's [Geometry],
+/// as all slopes and invisible walls are collapsed into one type each
+#[allow(unused)]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    Air = 0,
+    Solid = 1,
+    Slope = 2,
+    Floor = 3,
+    ShortcutEntrance = 4,
+}
+
+/// A single tile of a compiled room
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompiledTile {
+    pub terrain: Terrain,
+    /// Features which survive compilation, sorted by their editor code
+    pub features: Vec<Feature>,
+    /// Layer 2 is solid behind this tile
+    pub wall_behind: bool,
+}
+
+#[derive(Debug)]
+pub struct CompiledRoom {
+    name: String,
+    /// (Width, Height) dimensions of the room
+    dimensions: (usize, usize),
+    /// -1 if the room has no water
+    water_level: isize,
+    water_in_front: bool,
+    /// Bottom-left corner of each camera, in game pixels
+    cameras: Vec<Point>,
+    /// 2D array projected into 1D, indexed as `y * width + x`
+    tiles: Vec<CompiledTile>,
+}
+
+/// A single way in which a project disagrees with its compiled room
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Discrepancy {
+    Dimensions { project: (usize, usize), compiled: (usize, usize) },
+    Tile { x: usize, y: usize, project: CompiledTile, compiled: CompiledTile },
+    WaterLevel { project: isize, compiled: isize },
+    WaterInFront { project: bool, compiled: bool },
+    CameraCount { project: usize, compiled: usize },
+    /// Both positions are in game pixels
+    Camera { index: usize, project: Point, compiled: Point },
+}
+
+#[derive(Clone, Debug)]
+pub struct StaleReport {
+    pub room: String,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// Outcome of checking one project of a region folder
+#[derive(Debug)]
+pub enum StaleCheck {
+    Checked(StaleReport),
+    /// No compiled room with a matching name was found
+    NotRendered,
+    ProjectUnreadable,
+    RoomUnreadable(PathBuf),
+}
+
+impl Terrain {
+    pub fn from_data(data: u8) -> Option<Self> {
+        Some(match data {
+            0 => Self::Air,
+            1 => Self::Solid,
+            2 => Self::Slope,
+            3 => Self::Floor,
+            4 => Self::ShortcutEntrance,
+            _ => return None,
+        })
+    }
+
+    pub fn from_geometry(geometry: Geometry) -> Self {
+        match geometry {
+            Geometry::Air => Self::Air,
+            Geometry::Wall | Geometry::Glass => Self::Solid,
+            Geometry::SlopeSW | Geometry::SlopeSE | Geometry::SlopeNW | Geometry::SlopeNE => Self::Slope,
+            Geometry::Floor => Self::Floor,
+            Geometry::ShortcutEntrance => Self::ShortcutEntrance,
+        }
+    }
+}
+
+impl CompiledTile {
+    /// The tile the renderer would produce for (x, y) of the level
+    pub fn from_level(level: &RWLevel, x: usize, y: usize) -> Option<Self> {
+        let fg = level.tile(RWLevel::L1_FG, x, y)?;
+        let mg = level.tile(RWLevel::L2_MG, x, y)?;
+
+        let mut tile = Self {
+            terrain: Terrain::from_geometry(fg.geometry),
+            features: fg.features()
+                .iter()
+                .filter(|f| compiled_code(*f).is_some())
+                .collect(),
+            wall_behind: mg.geometry == Geometry::Wall,
+        };
+        tile.normalize();
+
+        Some(tile)
+    }
+
+    /// Parse a `terrain,code,code` entry of the geometry line
+    fn parse(entry: &str) -> Option<Self> {
+        let mut codes = entry.split(',').map(|code| code.trim().parse::<u8>().ok());
+        let terrain = Terrain::from_data(codes.next()??)?;
+
+        let mut tile = Self {
+            terrain,
+            features: vec![],
+            wall_behind: false,
+        };
+
+        for code in codes {
+            match code? {
+                6 => tile.wall_behind = true,
+                code => {
+                    // Codes the game knows about but the editor cannot produce are ignored
+                    if let Some(feature) = feature_from_compiled(code) {
+                        tile.features.push(feature);
+                    }
+                }
+            }
+        }
+        tile.normalize();
+
+        Some(tile)
+    }
+
+    /// The game only stores one shortcut type per tile, so a path dot is
+    /// dropped wherever something more specific occupies the same tile
+    fn normalize(&mut self) {
+        let specific = self.terrain == Terrain::ShortcutEntrance
+            || self.features.iter().any(|f| matches!(f,
+                Feature::RoomEntrance | Feature::CreatureDen | Feature::ScavengerHole | Feature::GopherHole
+            ));
+        if specific {
+            self.features.retain(|f| *f != Feature::ShortcutPathDot);
+        }
+
+        self.features.sort_by_key(|f| *f as u8);
+        self.features.dedup();
+    }
+}
+
+/// Map an editor feature to the code the renderer writes for it, if it is written at all
+fn compiled_code(feature: Feature) -> Option<u8> {
+    Some(match feature {
+        Feature::VPole => 1,
+        Feature::HPole => 2,
+        Feature::ShortcutPathDot => 3,
+        Feature::RoomEntrance => 4,
+        Feature::CreatureDen => 5,
+        Feature::Hive => 7,
+        Feature::Waterfall => 8,
+        Feature::ScavengerHole => 9,
+        Feature::WormGrass => 10,
+        Feature::GopherHole => 11,
+        Feature::GarbageWormDen => 12,
+        _ => return None,
+    })
+}
+
+fn feature_from_compiled(code: u8) -> Option<Feature> {
+    Some(match code {
+        1 => Feature::VPole,
+        2 => Feature::HPole,
+        3 => Feature::ShortcutPathDot,
+        4 => Feature::RoomEntrance,
+        5 => Feature::CreatureDen,
+        7 => Feature::Hive,
+        8 => Feature::Waterfall,
+        9 => Feature::ScavengerHole,
+        10 => Feature::WormGrass,
+        11 => Feature::GopherHole,
+        12 => Feature::GarbageWormDen,
+        _ => return None,
+    })
+}
+
+/// Convert a camera from the editor (top-left corner, Y down)
+/// to the game (bottom-left corner, Y up)
+pub fn camera_to_game(camera: Point, height: usize) -> Point {
+    Point {
+        fst: camera.fst,
+        snd: height as isize * TILE_PX - camera.snd - CAMERA_HEIGHT_PX,
+    }
+}
+
+impl CompiledRoom {
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let contents = read_to_string(path).ok()?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Option<Self> {
+        let lines: Vec<&str> = contents.lines().collect();
+        let name = lines.first()?.trim().to_owned();
+
+        // `width*height|waterLevel|waterInFront`, where the water fields may be missing
+        let mut size_line = lines.get(1)?.split('|');
+        let (w, h) = size_line.next()?.split_once('*')?;
+        let dimensions: (usize, usize) = (w.trim().parse().ok()?, h.trim().parse().ok()?);
+        let water_level = size_line.next()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(-1);
+        let water_in_front = size_line.next()
+            .is_some_and(|v| v.trim() == "1");
+
+        let cameras = lines.get(3)?
+            .split('|')
+            .filter(|cam| !cam.trim().is_empty())
+            .map(|cam| {
+                let (x, y) = cam.split_once(',')?;
+                let x: f64 = x.trim().parse().ok()?;
+                let y: f64 = y.trim().parse().ok()?;
+                Some(Point { fst: x.round() as isize, snd: y.round() as isize })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        // Tiles are written column by column, top to bottom, each terminated by `|`
+        let (w, h) = dimensions;
+        let mut entries = lines.get(GEOMETRY_LINE)?
+            .split('|')
+            .filter(|entry| !entry.trim().is_empty());
+        let mut columns: Vec<CompiledTile> = Vec::with_capacity(w * h);
+        for _ in 0..w * h {
+            columns.push(CompiledTile::parse(entries.next()?)?);
+        }
+
+        let tiles = (0..w * h)
+            .map(|idx| {
+                let (x, y) = (idx % w, idx / w);
+                columns[x * h + y].clone()
+            })
+            .collect();
+
+        Some(Self {
+            name,
+            dimensions,
+            water_level,
+            water_in_front,
+            cameras,
+            tiles,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.dimensions
+    }
+
+    pub fn water_level(&self) -> isize {
+        self.water_level
+    }
+
+    pub fn water_in_front(&self) -> bool {
+        self.water_in_front
+    }
+
+    pub fn cameras(&self) -> &[Point] {
+        &self.cameras
+    }
+
+    pub fn tile(&self, x: usize, y: usize) -> Option<&CompiledTile> {
+        let (w, h) = self.dimensions;
+        if x >= w || y >= h {
+            return None
+        }
+
+        self.tiles.get(y * w + x)
+    }
+}
+
+impl StaleReport {
+    pub fn is_stale(&self) -> bool {
+        !self.discrepancies.is_empty()
+    }
+}
+
+/// Compare a project against its compiled room, reporting everything that
+/// would change if the project were rendered again
+pub fn stale_check(level: &RWLevel, room: &CompiledRoom) -> StaleReport {
+    let mut discrepancies = Vec::new();
+    let meta = level.meta();
+
+    let (w, h) = meta.dimensions();
+    if (w, h) != room.dimensions() {
+        discrepancies.push(Discrepancy::Dimensions {
+            project: (w, h),
+            compiled: room.dimensions(),
+        });
+    } else {
+        for y in 0..h {
+            for x in 0..w {
+                let (Some(project), Some(compiled)) = (CompiledTile::from_level(level, x, y), room.tile(x, y)) else {
+                    continue;
+                };
+
+                if project != *compiled {
+                    discrepancies.push(Discrepancy::Tile {
+                        x,
+                        y,
+                        project,
+                        compiled: compiled.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    if meta.water_level() != room.water_level() {
+        discrepancies.push(Discrepancy::WaterLevel {
+            project: meta.water_level(),
+            compiled: room.water_level(),
+        });
+    } else if meta.water_level() != -1 && meta.water_in_front() != room.water_in_front() {
+        discrepancies.push(Discrepancy::WaterInFront {
+            project: meta.water_in_front(),
+            compiled: room.water_in_front(),
+        });
+    }
+
+    if meta.cameras().len() != room.cameras().len() {
+        discrepancies.push(Discrepancy::CameraCount {
+            project: meta.cameras().len(),
+            compiled: room.cameras().len(),
+        });
+    }
+
+    for (index, (cam, compiled)) in meta.cameras().iter().zip(room.cameras()).enumerate() {
+        let project = camera_to_game(*cam, h);
+        if project != *compiled {
+            discrepancies.push(Discrepancy::Camera {
+                index,
+                project,
+                compiled: *compiled,
+            });
+        }
+    }
+
+    StaleReport {
+        room: level.name().to_owned(),
+        discrepancies,
+    }
+}
+
+/// Check every project in `projects` against the compiled room of the same
+/// name (ignoring case) in `rooms`, e.g. `LevelEditorProjects/SU` and `world/su-rooms`
+pub fn stale_check_dir<P: AsRef<Path>, Q: AsRef<Path>>(projects: P, rooms: Q) -> Vec<(PathBuf, StaleCheck)> {
+    let compiled: Vec<PathBuf> = txt_files(rooms.as_ref());
+    let mut results: Vec<(PathBuf, StaleCheck)> = txt_files(projects.as_ref())
+        .into_iter()
+        .map(|project| {
+            let stem = project.file_stem().map(|s| s.to_string_lossy().to_lowercase());
+            let room = compiled.iter()
+                .find(|room| room.file_stem().map(|s| s.to_string_lossy().to_lowercase()) == stem);
+
+            let check = match (room, RWLevel::load(&project)) {
+                (None, _) => StaleCheck::NotRendered,
+                (_, None) => StaleCheck::ProjectUnreadable,
+                (Some(room), Some(level)) => match CompiledRoom::load(room) {
+                    Some(compiled) => StaleCheck::Checked(stale_check(&level, &compiled)),
+                    None => StaleCheck::RoomUnreadable(room.clone()),
+                },
+            };
+
+            (project, check)
+        })
+        .collect();
+
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    results
+}
+
+fn txt_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = read_dir(dir) else {
+        return vec![];
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("txt")))
+        .collect()
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dimensions { project, compiled } =>
+                write!(f, "size is {}x{} in the project but {}x{} when rendered", project.0, project.1, compiled.0, compiled.1),
+            Self::Tile { x, y, project, compiled } =>
+                write!(f, "tile ({x}, {y}) is {project:?} in the project but {compiled:?} when rendered"),
+            Self::WaterLevel { project, compiled } =>
+                write!(f, "water level is {project} in the project but {compiled} when rendered"),
+            Self::WaterInFront { project, compiled } =>
+                write!(f, "water in front is {project} in the project but {compiled} when rendered"),
+            Self::CameraCount { project, compiled } =>
+                write!(f, "{project} cameras in the project but {compiled} when rendered"),
+            Self::Camera { index, project, compiled } =>
+                write!(f, "camera {index} is at ({}, {}) in the project but ({}, {}) when rendered", project.fst, project.snd, compiled.fst, compiled.snd),
+        }
+    }
+}
+
+impl fmt::Display for StaleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_stale() {
+            return write!(f, "{}: up to date", self.room)
+        }
+
+        write!(f, "{}: stale ({} differences)", self.room, self.discrepancies.len())?;
+        for discrepancy in &self.discrepancies {
+            write!(f, "\n  {discrepancy}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rwlevel::fixture;
+
+    use super::{stale_check, CompiledRoom, Discrepancy, Terrain};
+
+    const ROWS: &[&str] = &[
+        "#..",
+        "#|#",
+    ];
+
+    fn compiled(water: &str, geometry: &str) -> CompiledRoom {
+        let text = format!("TEST\n3*2|{water}|1\n0.0000*1.0000|0|0\n0,-760\nBorder: Passable\n\n\n\n\n\n\n{geometry}\n");
+        CompiledRoom::parse(&text).expect("Bad compiled room")
+    }
+
+    #[test]
+    fn test_up_to_date() {
+        let level = fixture::level(ROWS);
+        let room = compiled("-1", "1|1|0|0,1|0|1|");
+
+        let report = stale_check(&level, &room);
+        assert!(!report.is_stale(), "{report}");
+    }
+
+    #[test]
+    fn test_stale() {
+        let level = fixture::level(ROWS);
+        let room = compiled("3", "1|1|0|0|0|0|");
+
+        let report = stale_check(&level, &room);
+        assert_eq!(report.discrepancies.len(), 3, "{report}");
+        assert!(matches!(report.discrepancies[0], Discrepancy::Tile { x: 1, y: 1, .. }));
+        assert!(matches!(
+            &report.discrepancies[1],
+            Discrepancy::Tile { x: 2, y: 1, project, compiled }
+                if project.terrain == Terrain::Solid && compiled.terrain == Terrain::Air
+        ));
+        assert_eq!(report.discrepancies[2], Discrepancy::WaterLevel { project: -1, compiled: 3 });
+    }
+}
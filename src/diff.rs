@@ -0,0 +1,569 @@
+//! Semantic differences between two versions of a level, for reviewing changes
+//! which are unreadable in the project file itself.
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::{rwlevel::{canonical::is_editor_state, lingo_to_json::ProjectJson}, Effect, Point, RWLevel, Tile};
+
+/// Lines of the project compared key by key, with the keys which are covered by
+/// other changes. Editor state is never compared
+pub(crate) const SETTINGS: [(&str, &[&str]); 5] = [
+    ("light", &[]),
+    ("level", &[]),
+    ("render", &["#size"]),
+    ("cameras", &["#cameras", "#quads"]),
+    ("water", &[]),
+];
+
+pub(crate) fn settings_line<'a>(project: &'a ProjectJson, line: &str) -> &'a Value {
+    match line {
+        "light" => &project._lights,
+        "level" => &project._settings1,
+        "render" => &project._settings2,
+        "cameras" => &project._cams,
+        "water" => &project._water,
+        _ => unreachable!("Unknown settings line {line}"),
+    }
+}
+
+pub(crate) fn settings_line_mut<'a>(project: &'a mut ProjectJson, line: &str) -> &'a mut Value {
+    match line {
+        "light" => &mut project._lights,
+        "level" => &mut project._settings1,
+        "render" => &mut project._settings2,
+        "cameras" => &mut project._cams,
+        "water" => &mut project._water,
+        _ => unreachable!("Unknown settings line {line}"),
+    }
+}
+
+/// A single change between two levels. Effect and prop indices are positions
+/// in the old level for removals, and in the new level otherwise
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Dimensions { old: (usize, usize), new: (usize, usize) },
+    Tile { layer: usize, x: usize, y: usize, old: Tile, new: Tile },
+    /// A cell of the tile editor's matrix, holding the placed tile or material
+    TileArt { layer: usize, x: usize, y: usize, old: Value, new: Value },
+    EffectAdded { index: usize, effect: Effect },
+    EffectRemoved { index: usize, name: String },
+    EffectMoved { name: String, from: usize, to: usize },
+    /// Cells of an effect's matrix which changed, as `(x, y, old, new)`
+    EffectMatrix { index: usize, name: String, cells: Vec<(usize, usize, f64, f64)> },
+    /// Options or other fields of an effect. Both values are the effect's entry without its matrix
+    EffectSettings { index: usize, name: String, old: Value, new: Value },
+    CameraAdded { index: usize, pos: Point, quad: Value },
+    CameraRemoved { index: usize, pos: Point },
+    CameraMoved { index: usize, old: Point, new: Point },
+    /// The corners of a camera were bent
+    CameraQuad { index: usize, old: Value, new: Value },
+    PropAdded { index: usize, prop: Value },
+    PropRemoved { index: usize, prop: Value },
+    PropChanged { old_index: usize, index: usize, old: Value, new: Value },
+    /// A key of one of the settings lines, None when the key is missing
+    Setting { line: &'static str, key: String, old: Option<Value>, new: Option<Value> },
+}
+
+/// Everything which changed between two levels, in the order tiles, effects,
+/// cameras, props, then settings
+#[derive(Clone, Debug, Default)]
+pub struct LevelDiff {
+    pub(crate) changes: Vec<Change>,
+}
+
+impl LevelDiff {
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// An array with an object per change, each with a `change` field naming its kind.
+    /// Layers are 1-based as in the editor
+    pub fn to_json(&self) -> Value {
+        self.changes.iter().map(Change::to_json).collect()
+    }
+}
+
+/// Compare two levels. When the dimensions differ, the rest of the changes are
+/// relative to the old level after [RWLevel::resize] to the new dimensions
+pub fn diff(old: &RWLevel, new: &RWLevel) -> LevelDiff {
+    let mut changes = vec![];
+
+    let (old_dims, new_dims) = (old.meta().dimensions(), new.meta().dimensions());
+    let resized;
+    let old = match old_dims == new_dims {
+        true => old,
+        false => {
+            changes.push(Change::Dimensions { old: old_dims, new: new_dims });
+            let mut level = old.clone();
+            level.resize(new_dims.0, new_dims.1);
+            resized = level;
+            &resized
+        }
+    };
+    let (w, h) = new_dims;
+    let (old_project, new_project) = (old.to_project(), new.to_project());
+
+    for layer in 0..3 {
+        for y in 0..h {
+            for x in 0..w {
+                let (a, b) = (old.tile(layer, x, y), new.tile(layer, x, y));
+                if let (Some(a), Some(b)) = (a, b) {
+                    if a != b {
+                        changes.push(Change::Tile { layer, x, y, old: a, new: b });
+                    }
+                }
+            }
+        }
+    }
+
+    let (old_art, new_art) = (old_project._tiles.get("#tlMatrix"), new_project._tiles.get("#tlMatrix"));
+    for layer in 0..3 {
+        for y in 0..h {
+            for x in 0..w {
+                let cell = |matrix: Option<&Value>| matrix
+                    .and_then(|m| m.get(x)?.get(y)?.get(layer))
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                let (a, b) = (cell(old_art), cell(new_art));
+                if a != b {
+                    changes.push(Change::TileArt { layer, x, y, old: a, new: b });
+                }
+            }
+        }
+    }
+
+    diff_effects(old.effects(), new.effects(), (w, h), &mut changes);
+    diff_cameras(&old_project, &new_project, old, new, &mut changes);
+    diff_props(&old_project, &new_project, &mut changes);
+
+    for (line, skipped) in SETTINGS {
+        let (a, b) = (settings_line(&old_project, line), settings_line(&new_project, line));
+        let keys = a.as_object().into_iter().flatten()
+            .chain(b.as_object().into_iter().flatten())
+            .map(|(key, _)| key)
+            .filter(|key| !skipped.contains(&key.as_str()) && !is_editor_state(line, key));
+
+        let mut seen = vec![];
+        for key in keys {
+            if seen.contains(&key) {
+                continue
+            }
+            seen.push(key);
+
+            let (old, new) = (a.get(key), b.get(key));
+            if old != new {
+                changes.push(Change::Setting { line, key: key.clone(), old: old.cloned(), new: new.cloned() });
+            }
+        }
+    }
+
+    LevelDiff { changes }
+}
+
+/// Pairs of indices of the same effects in two lists. Effects are matched by
+/// name, the nth effect of a name in one list with the nth of the same name in the other
+pub(crate) fn match_effects(old: &[Effect], new: &[Effect]) -> Vec<(usize, usize)> {
+    let occurrence = |effects: &[Effect], idx: usize| effects[..idx].iter()
+        .filter(|e| e.name() == effects[idx].name())
+        .count();
+
+    old.iter()
+        .enumerate()
+        .filter_map(|(oi, effect)| {
+            let nth = occurrence(old, oi);
+            (0..new.len())
+                .filter(|&ni| new[ni].name() == effect.name())
+                .nth(nth)
+                .map(|ni| (oi, ni))
+        })
+        .collect()
+}
+
+/// Only effects which left the longest run of matched effects in the same order
+/// are reported as moved
+fn diff_effects(old: &[Effect], new: &[Effect], (w, h): (usize, usize), changes: &mut Vec<Change>) {
+    let matched = match_effects(old, new);
+
+    for (oi, effect) in old.iter().enumerate() {
+        if !matched.iter().any(|&(o, _)| o == oi) {
+            changes.push(Change::EffectRemoved { index: oi, name: effect.name().to_owned() });
+        }
+    }
+
+    for (ni, effect) in new.iter().enumerate() {
+        if !matched.iter().any(|&(_, n)| n == ni) {
+            changes.push(Change::EffectAdded { index: ni, effect: effect.clone() });
+        }
+    }
+
+    let in_order = longest_increasing(&matched.iter().map(|&(_, ni)| ni).collect::<Vec<_>>());
+    for (idx, &(oi, ni)) in matched.iter().enumerate() {
+        if !in_order.contains(&idx) {
+            changes.push(Change::EffectMoved { name: new[ni].name().to_owned(), from: oi, to: ni });
+        }
+    }
+
+    for &(oi, ni) in &matched {
+        let (a, b) = (&old[oi], &new[ni]);
+        let name = b.name().to_owned();
+
+        let mut cells = vec![];
+        for x in 0..w {
+            for y in 0..h {
+                let value = |effect: &Effect| effect.matrix().get(x, y).copied().unwrap_or_default();
+                let (va, vb) = (value(a), value(b));
+                if va != vb {
+                    cells.push((x, y, va, vb));
+                }
+            }
+        }
+        if !cells.is_empty() {
+            changes.push(Change::EffectMatrix { index: ni, name: name.clone(), cells });
+        }
+
+        let (sa, sb) = (effect_settings(a), effect_settings(b));
+        if sa != sb {
+            changes.push(Change::EffectSettings { index: ni, name, old: sa, new: sb });
+        }
+    }
+}
+
+pub(crate) fn effect_settings(effect: &Effect) -> Value {
+    let mut entry = effect.to_json();
+    if let Some(map) = entry.as_object_mut() {
+        map.shift_remove("#mtrx");
+    }
+
+    entry
+}
+
+/// Indices of a longest strictly increasing subsequence
+pub(crate) fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // Length of the longest run ending at each index, and the index before it
+    let mut best: Vec<(usize, Option<usize>)> = vec![];
+    for (i, value) in values.iter().enumerate() {
+        let prev = (0..i)
+            .filter(|&j| values[j] < *value)
+            .max_by_key(|&j| best[j].0);
+        best.push((prev.map_or(1, |j| best[j].0 + 1), prev));
+    }
+
+    let mut out = vec![];
+    let mut cur = (0..values.len()).max_by_key(|&i| best[i].0);
+    while let Some(i) = cur {
+        out.push(i);
+        cur = best[i].1;
+    }
+
+    out
+}
+
+fn diff_cameras(old_project: &ProjectJson, new_project: &ProjectJson, old: &RWLevel, new: &RWLevel, changes: &mut Vec<Change>) {
+    let (a, b) = (old.meta().cameras(), new.meta().cameras());
+    let quad = |project: &ProjectJson, idx: usize| project._cams.get("#quads")
+        .and_then(|q| q.get(idx))
+        .cloned()
+        .unwrap_or(Value::Null);
+
+    for idx in 0..a.len().max(b.len()) {
+        match (a.get(idx), b.get(idx)) {
+            (Some(old), Some(new)) => {
+                if old != new {
+                    changes.push(Change::CameraMoved { index: idx, old: *old, new: *new });
+                }
+                let (qa, qb) = (quad(old_project, idx), quad(new_project, idx));
+                if qa != qb {
+                    changes.push(Change::CameraQuad { index: idx, old: qa, new: qb });
+                }
+            }
+            (Some(pos), None) => changes.push(Change::CameraRemoved { index: idx, pos: *pos }),
+            (None, Some(pos)) => changes.push(Change::CameraAdded { index: idx, pos: *pos, quad: quad(new_project, idx) }),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn diff_props(old_project: &ProjectJson, new_project: &ProjectJson, changes: &mut Vec<Change>) {
+    let (a, b) = (props(old_project), props(new_project));
+    let matched = match_props(&a, &b);
+
+    for &(oi, ni) in &matched {
+        if a[oi] != b[ni] {
+            changes.push(Change::PropChanged { old_index: oi, index: ni, old: a[oi].clone(), new: b[ni].clone() });
+        }
+    }
+
+    for (oi, prop) in a.iter().enumerate() {
+        if !matched.iter().any(|&(o, _)| o == oi) {
+            changes.push(Change::PropRemoved { index: oi, prop: prop.clone() });
+        }
+    }
+
+    for (ni, prop) in b.iter().enumerate() {
+        if !matched.iter().any(|&(_, n)| n == ni) {
+            changes.push(Change::PropAdded { index: ni, prop: prop.clone() });
+        }
+    }
+}
+
+pub(crate) fn props(project: &ProjectJson) -> Vec<Value> {
+    project._props.get("#props")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Pairs of indices of the same props in two lists, sorted by the first index.
+/// Props which are unchanged are found as the longest common subsequence of the
+/// two lists. Of the rest, props with the same name are paired up in order
+pub(crate) fn match_props(a: &[Value], b: &[Value]) -> Vec<(usize, usize)> {
+    // lcs[i][j] is the length of the common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut matched = vec![];
+    let (mut removed, mut added) = (vec![], vec![]);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            matched.push((i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            removed.push(i);
+            i += 1;
+        } else {
+            added.push(j);
+            j += 1;
+        }
+    }
+
+    for oi in removed {
+        let pair = added.iter().position(|&ni| b[ni].get(1) == a[oi].get(1));
+        if let Some(p) = pair {
+            matched.push((oi, added.remove(p)));
+        }
+    }
+
+    matched.sort();
+    matched
+}
+
+impl Change {
+    pub fn to_json(&self) -> Value {
+        let point = |p: &Point| json!([p.fst, p.snd]);
+
+        match self {
+            Self::Dimensions { old, new } =>
+                json!({ "change": "dimensions", "old": [old.0, old.1], "new": [new.0, new.1] }),
+            Self::Tile { layer, x, y, old, new } =>
+                json!({ "change": "tile", "layer": layer + 1, "x": x, "y": y, "old": old.to_json(), "new": new.to_json() }),
+            Self::TileArt { layer, x, y, old, new } =>
+                json!({ "change": "tileArt", "layer": layer + 1, "x": x, "y": y, "old": old, "new": new }),
+            Self::EffectAdded { index, effect } =>
+                json!({ "change": "effectAdded", "index": index, "name": effect.name(), "effect": effect.to_json() }),
+            Self::EffectRemoved { index, name } =>
+                json!({ "change": "effectRemoved", "index": index, "name": name }),
+            Self::EffectMoved { name, from, to } =>
+                json!({ "change": "effectMoved", "name": name, "from": from, "to": to }),
+            Self::EffectMatrix { index, name, cells } =>
+                json!({
+                    "change": "effectMatrix",
+                    "index": index,
+                    "name": name,
+                    "cells": cells.iter().map(|(x, y, old, new)| json!({ "x": x, "y": y, "old": old, "new": new })).collect::<Vec<_>>(),
+                }),
+            Self::EffectSettings { index, name, old, new } =>
+                json!({ "change": "effectSettings", "index": index, "name": name, "old": old, "new": new }),
+            Self::CameraAdded { index, pos, quad } =>
+                json!({ "change": "cameraAdded", "index": index, "pos": point(pos), "quad": quad }),
+            Self::CameraRemoved { index, pos } =>
+                json!({ "change": "cameraRemoved", "index": index, "pos": point(pos) }),
+            Self::CameraMoved { index, old, new } =>
+                json!({ "change": "cameraMoved", "index": index, "old": point(old), "new": point(new) }),
+            Self::CameraQuad { index, old, new } =>
+                json!({ "change": "cameraQuad", "index": index, "old": old, "new": new }),
+            Self::PropAdded { index, prop } =>
+                json!({ "change": "propAdded", "index": index, "prop": prop }),
+            Self::PropRemoved { index, prop } =>
+                json!({ "change": "propRemoved", "index": index, "prop": prop }),
+            Self::PropChanged { old_index, index, old, new } =>
+                json!({ "change": "propChanged", "oldIndex": old_index, "index": index, "old": old, "new": new }),
+            Self::Setting { line, key, old, new } =>
+                json!({ "change": "setting", "line": line, "key": key, "old": old, "new": new }),
+        }
+    }
+}
+
+/// Short description of a tile, such as `Air [VPole, HPole]`
+pub(crate) struct TileDesc<'a>(pub(crate) &'a Tile);
+
+impl fmt::Display for TileDesc<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0.geometry)?;
+        if !self.0.features().is_empty() {
+            write!(f, " {:?}", self.0.features())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Short description of a cell of the tile editor's matrix
+fn describe_art(cell: &Value) -> String {
+    let data = cell.get("#Data");
+    match cell.get("#tp").and_then(Value::as_str) {
+        Some("default") | None => "nothing".into(),
+        Some("material") => format!("material {}", data.and_then(Value::as_str).unwrap_or("?")),
+        Some("tileHead") => format!("tile {}", data.and_then(|d| d.get(1)).and_then(Value::as_str).unwrap_or("?")),
+        Some("tileBody") => format!("part of the tile at {}", data.and_then(|d| d.get(0)).and_then(Value::as_str).unwrap_or("?")),
+        Some(other) => other.to_owned(),
+    }
+}
+
+fn prop_name(prop: &Value) -> &str {
+    prop.get(1).and_then(Value::as_str).unwrap_or("?")
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |v: &Option<Value>| v.as_ref().map_or("(none)".into(), Value::to_string);
+
+        match self {
+            Self::Dimensions { old, new } =>
+                write!(f, "size: {}x{} -> {}x{}", old.0, old.1, new.0, new.1),
+            Self::Tile { layer, x, y, old, new } =>
+                write!(f, "L{} ({x}, {y}): {} -> {}", layer + 1, TileDesc(old), TileDesc(new)),
+            Self::TileArt { layer, x, y, old, new } =>
+                write!(f, "L{} ({x}, {y}) tiles: {} -> {}", layer + 1, describe_art(old), describe_art(new)),
+            Self::EffectAdded { index, effect } =>
+                write!(f, "effect {index} {}: added", effect.name()),
+            Self::EffectRemoved { index, name } =>
+                write!(f, "effect {index} {name}: removed"),
+            Self::EffectMoved { name, from, to } =>
+                write!(f, "effect {name}: moved from {from} to {to}"),
+            Self::EffectMatrix { index, name, cells } => {
+                let max = cells.iter().map(|(_, _, a, b)| (b - a).abs()).fold(0.0, f64::max);
+                let (x0, y0) = cells.iter().fold((usize::MAX, usize::MAX), |(x0, y0), &(x, y, _, _)| (x0.min(x), y0.min(y)));
+                let (x1, y1) = cells.iter().fold((0, 0), |(x1, y1), &(x, y, _, _)| (x1.max(x), y1.max(y)));
+                write!(f, "effect {index} {name}: {} cells changed by up to {max:.1} within ({x0}, {y0})-({x1}, {y1})", cells.len())
+            }
+            Self::EffectSettings { index, name, .. } =>
+                write!(f, "effect {index} {name}: options changed"),
+            Self::CameraAdded { index, pos, .. } =>
+                write!(f, "camera {index}: added at ({}, {})", pos.fst, pos.snd),
+            Self::CameraRemoved { index, pos } =>
+                write!(f, "camera {index}: removed from ({}, {})", pos.fst, pos.snd),
+            Self::CameraMoved { index, old, new } =>
+                write!(f, "camera {index}: moved ({}, {}) -> ({}, {})", old.fst, old.snd, new.fst, new.snd),
+            Self::CameraQuad { index, .. } =>
+                write!(f, "camera {index}: corners changed"),
+            Self::PropAdded { index, prop } =>
+                write!(f, "prop {index} {}: added", prop_name(prop)),
+            Self::PropRemoved { index, prop } =>
+                write!(f, "prop {index} {}: removed", prop_name(prop)),
+            Self::PropChanged { old_index, index, old, new } => {
+                let fields: Vec<&str> = ["depth", "name", "kind", "position", "settings"]
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(i, _)| old.get(i) != new.get(i))
+                    .map(|(_, field)| field)
+                    .collect();
+                let moved = if old_index != index { format!(" (was {old_index})") } else { String::new() };
+                write!(f, "prop {index}{moved} {}: {} changed", prop_name(new), fields.join(", "))
+            }
+            Self::Setting { line, key, old, new } =>
+                write!(f, "{line} setting {}: {} -> {}", key.trim_start_matches('#'), value(old), value(new)),
+        }
+    }
+}
+
+impl fmt::Display for LevelDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, change) in self.changes.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{change}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{rwlevel::fixture::{self, Edit}, RWLevel};
+
+    use super::{diff, longest_increasing, Change};
+
+    /// Project with the given effects, each with an empty matrix
+    fn with_effects(rows: &[&str], effects: &[&str]) -> String {
+        let entries: Vec<String> = effects.iter()
+            .map(|name| format!(r#"[#nm: "{name}", #mtrx: [[0, 0], [0, 0], [0, 0]], #Options: []]"#))
+            .collect();
+        fixture::project(rows).edit("#effects: []", &format!("#effects: [{}]", entries.join(", ")))
+    }
+
+    #[test]
+    fn test_tiles_and_effects() {
+        let old = with_effects(&["#.#", "#>#"], &["Slime", "Rust", "Moss"]);
+        let new = with_effects(&["#|#", "#>#"], &["Moss", "Slime", "Rust", "Slime"])
+            .replacen("#mtrx: [[0, 0], [0, 0]", "#mtrx: [[0, 0], [50, 0]", 1);
+        let old = RWLevel::from_project_str("OLD", &old).unwrap();
+        let new = RWLevel::from_project_str("NEW", &new).unwrap();
+
+        let changes = diff(&old, &new).changes().to_vec();
+        assert!(matches!(&changes[0], Change::Tile { layer: 0, x: 1, y: 0, .. }));
+        assert_eq!(changes[0].to_string(), "L1 (1, 0): Air -> Air [VPole]");
+        assert!(matches!(&changes[1], Change::EffectAdded { index: 3, .. }));
+        assert_eq!(changes[2], Change::EffectMoved { name: "Moss".into(), from: 2, to: 0 });
+        assert!(matches!(&changes[3], Change::EffectMatrix { index: 0, cells, .. } if cells == &[(1, 0, 0.0, 50.0)]));
+        assert_eq!(changes.len(), 4);
+
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_props_and_settings() {
+        let level = |props: &str, wave: usize| {
+            let project = fixture::project(&["#"])
+                .edit("#props: []", &format!("#props: [{props}]"))
+                .edit("#waveLength: 60", &format!("#waveLength: {wave}"));
+            RWLevel::from_project_str("TEST", &project).unwrap()
+        };
+        let pipe = r#"[-5, "Pipe", point(1, 1), [], [#seed: 1]]"#;
+        let rock = r#"[-2, "Rock", point(1, 2), [], [#seed: 1]]"#;
+        let moved_rock = r#"[-3, "Rock", point(1, 2), [], [#seed: 1]]"#;
+
+        let old = level(&format!("{pipe}, {rock}"), 60);
+        let new = level(&format!("{moved_rock}, {pipe}"), 40);
+
+        let changes = diff(&old, &new).to_json();
+        let kinds: Vec<&str> = changes.as_array().unwrap().iter().map(|c| c["change"].as_str().unwrap()).collect();
+        assert_eq!(kinds, ["propChanged", "setting"]);
+        assert_eq!(changes[0]["oldIndex"], 1);
+        assert_eq!(changes[0]["index"], 0);
+        assert_eq!(changes[1]["key"], "#waveLength");
+        assert_eq!(changes[1]["new"], json!(40));
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        let mut run = longest_increasing(&[3, 0, 1, 2]);
+        run.sort();
+        assert_eq!(run, [1, 2, 3]);
+        assert!(longest_increasing(&[]).is_empty());
+    }
+}
@@ -1,8 +1,99 @@
 use serde_json::Value;
 
-#[derive(Debug)]
+use crate::grid::Grid;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Effect {
     pub(crate) name: String,
-    pub(crate) matrix: Vec<Vec<f64>>,
+    pub(crate) matrix: Grid<f64>,
+    #[cfg_attr(feature = "serde", serde(with = "json_text"))]
     pub(crate) options: Value, //TODO
-}
\ No newline at end of file
+    /// Full `#effects` entry from the project, keeping the fields not modelled here
+    #[cfg_attr(feature = "serde", serde(with = "json_text"))]
+    pub(crate) entry: Value,
+    /// Cells of `#mtrx` which were missing from a short column or weren't
+    /// numbers, and were read as 0
+    pub(crate) bad_cells: usize,
+}
+
+impl Effect {
+    /// Read an entry of the project's `#effects`. The matrix is as wide as its
+    /// columns and as tall as the tallest, with bad cells read as 0
+    pub(crate) fn from_entry(entry: &Value) -> Option<Self> {
+        let name = entry.get("#nm")?.as_str()?.to_owned();
+        let columns: Vec<&[Value]> = entry.get("#mtrx")?
+            .as_array()?
+            .iter()
+            .map(|column| column.as_array().map_or(&[][..], Vec::as_slice))
+            .collect();
+        let options = entry.get("#Options")?.clone();
+
+        let h = columns.iter().map(|column| column.len()).max().unwrap_or_default();
+        let mut bad_cells = 0;
+        let matrix = Grid::from_fn(columns.len(), h, |x, y| {
+            columns[x].get(y).and_then(Value::as_f64).unwrap_or_else(|| {
+                bad_cells += 1;
+                0.0
+            })
+        });
+
+        Some(Self {
+            name,
+            matrix,
+            options,
+            entry: entry.clone(),
+            bad_cells,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Strength of the effect per tile, from 0 to 100
+    pub fn matrix(&self) -> &Grid<f64> {
+        &self.matrix
+    }
+
+    pub fn options(&self) -> &Value {
+        &self.options
+    }
+
+    /// The effect's entry in the project, updated with the modelled fields
+    pub(crate) fn to_json(&self) -> Value {
+        let mut entry = self.entry.clone();
+        if let Some(map) = entry.as_object_mut() {
+            map.insert("#nm".into(), self.name.clone().into());
+            let columns = self.matrix.columns().map(|column| column.copied().map(cell_json).collect());
+            map.insert("#mtrx".into(), Value::Array(columns.collect()));
+            map.insert("#Options".into(), self.options.clone());
+        }
+
+        entry
+    }
+}
+
+/// A cell of a matrix, kept as an integer if it's whole as the editor writes them
+fn cell_json(cell: f64) -> Value {
+    match cell.fract() == 0.0 && cell.abs() < (1u64 << 53) as f64 {
+        true => Value::from(cell as i64),
+        false => Value::from(cell),
+    }
+}
+
+/// JSON values written as JSON text, which formats that don't describe their
+/// own types can read back
+#[cfg(feature = "serde")]
+mod json_text {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use serde_json::Value;
+
+    pub(super) fn serialize<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        serde_json::from_str(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
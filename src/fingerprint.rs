@@ -0,0 +1,158 @@
+//! Stable hashes of a level's content, for finding duplicate rooms and for
+//! deciding whether a room needs to be rendered again.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{rwlevel::canonical::is_editor_state, RWLevel};
+
+/// Hash of everything which makes up a level, ignoring its name, the editor's
+/// UI state and how the project file was formatted, including the order and
+/// repeats of each tile's features. The hash only changes
+/// between versions of this crate if the [VERSION](Fingerprint::VERSION) does
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// Bumped whenever the same level would get a different fingerprint
+    pub const VERSION: u32 = 2;
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// 64-bit FNV-1a, which unlike std's hashers is the same on every platform and release
+pub(crate) struct Fnv(pub(crate) u64);
+
+impl Fnv {
+    pub(crate) const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_len(&mut self, len: usize) {
+        self.write(&(len as u64).to_le_bytes());
+    }
+
+    /// Hash a value independently of how it was written. Lingo symbols are case
+    /// insensitive, so property names are lowercased, and the order of properties
+    /// doesn't matter. Numbers are compared to the 4 decimal places Lingo keeps
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.write(&[0]),
+            Value::Bool(b) => self.write(&[1, *b as u8]),
+            Value::Number(n) => {
+                let fixed = (n.as_f64().unwrap_or_default() * 10_000.0).round() as i64;
+                self.write(&[2]);
+                self.write(&fixed.to_le_bytes());
+            }
+            Value::String(s) => {
+                // Lingo data such as `color( 255, 0, 0 )` is written with varying spaces
+                let is_data = ["point(", "rect(", "color("].iter().any(|p| s.starts_with(p)) && s.ends_with(')');
+                let s = match is_data {
+                    true => s.replace(' ', ""),
+                    false => s.clone(),
+                };
+                self.write(&[3]);
+                self.write_len(s.len());
+                self.write(s.as_bytes());
+            }
+            Value::Array(items) => {
+                self.write(&[4]);
+                self.write_len(items.len());
+                for item in items {
+                    self.value(item);
+                }
+            }
+            Value::Object(map) => {
+                let mut entries: Vec<(String, &Value)> = map.iter().map(|(k, v)| (k.to_lowercase(), v)).collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+                self.write(&[5]);
+                self.write_len(entries.len());
+                for (key, value) in entries {
+                    self.write_len(key.len());
+                    self.write(key.as_bytes());
+                    self.value(value);
+                }
+            }
+        }
+    }
+}
+
+impl RWLevel {
+    /// A hash of the level's geometry, tiles, effects, props, cameras and settings
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut project = self.to_project();
+        project._geom = self.geometry_json(|layer, x, y| Some(layer.get(x, y)?.to_json()));
+        let lines = [
+            ("geometry", &project._geom),
+            ("tiles", &project._tiles),
+            ("effects", &project._effects),
+            ("light", &project._lights),
+            ("level", &project._settings1),
+            ("render", &project._settings2),
+            ("cameras", &project._cams),
+            ("water", &project._water),
+            ("props", &project._props),
+        ];
+
+        let mut hash = Fnv(Fnv::OFFSET);
+        hash.write(&Fingerprint::VERSION.to_le_bytes());
+        for (line, value) in lines {
+            match value {
+                Value::Object(map) => {
+                    let content = map.iter()
+                        .filter(|(key, _)| !is_editor_state(line, key))
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect();
+                    hash.value(&Value::Object(content));
+                }
+                other => hash.value(other),
+            }
+        }
+
+        Fingerprint(hash.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rwlevel::fixture::{self, Edit}, RWLevel};
+
+    const ROWS: &[&str] = &["#E#", "#o#", "#>."];
+
+    #[test]
+    fn test_fingerprint() {
+        let level = fixture::level(ROWS);
+        let fingerprint = level.fingerprint();
+        assert_eq!(fingerprint.to_string(), "78c5c4745b6da5e8");
+
+        // Editor state, names and formatting are ignored
+        let project = fixture::project(ROWS)
+            .edit("#editEffect: 0", "#editEffect: 4")
+            .edit("color( 255, 0, 0 )", "color(255, 0, 0)")
+            .edit("#flatness: 1", "#FLATNESS: 1.0000")
+            .edit("#lastTm: 0", "#LastTM: 12")
+            .replacen("[1, [6]]", "[1, [6, 6]]", 1);
+        let same = RWLevel::from_project_str("OTHER", &project).unwrap();
+        assert_eq!(same.fingerprint(), fingerprint);
+
+        let project = fixture::project(ROWS).edit("#waveSpeed: 10", "#waveSpeed: 11");
+        let changed = RWLevel::from_project_str("TEST", &project).unwrap();
+        assert_ne!(changed.fingerprint(), fingerprint);
+    }
+}
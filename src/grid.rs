@@ -0,0 +1,301 @@
+//! A rectangle of values indexed by `(x, y)`, used for the tiles of each layer
+//! and the matrices of effects.
+//!
+//! X runs left to right and Y top to bottom, as in the editor. Cells are stored
+//! row by row, so the cell at `(x, y)` is at `y * width + x`. The project file
+//! lists the same cells column by column, see [`Grid::from_columns`].
+
+mod fill;
+
+use std::{
+    iter::{Skip, StepBy},
+    ops::{Index, IndexMut},
+    slice::{self, ChunksExact},
+};
+
+pub use fill::{Region, Regions};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    /// Fill the grid by calling `f` with the `(x, y)` of each cell
+    pub fn from_fn(width: usize, height: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+
+        Self { width, height, cells }
+    }
+
+    /// A grid from a list of columns, each listing its cells from the top, as
+    /// the editor writes them. None if the columns aren't all the same height
+    pub fn from_columns(columns: Vec<Vec<T>>) -> Option<Self> {
+        let width = columns.len();
+        let height = columns.first().map_or(0, Vec::len);
+        if columns.iter().any(|column| column.len() != height) {
+            return None
+        }
+
+        let mut columns: Vec<_> = columns.into_iter().map(Vec::into_iter).collect();
+        let cells = (0..width * height)
+            .map(|idx| columns[idx % width].next().expect("Columns are the same height"))
+            .collect();
+
+        Some(Self { width, height, cells })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// (Width, Height)
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// The cell at (x, y), or None if out of bounds
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        self.contains(x, y).then(|| &self.cells[y * self.width + x])
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        self.contains(x, y).then(|| &mut self.cells[y * self.width + x])
+    }
+
+    /// Every cell as `(x, y, cell)`, row by row
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &T)> + '_ {
+        let width = self.width.max(1);
+        self.cells.iter()
+            .enumerate()
+            .map(move |(idx, cell)| (idx % width, idx / width, cell))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut T)> + '_ {
+        let width = self.width.max(1);
+        self.cells.iter_mut()
+            .enumerate()
+            .map(move |(idx, cell)| (idx % width, idx / width, cell))
+    }
+
+    /// Cells of the row, left to right
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        (y < self.height).then(|| &self.cells[y * self.width..(y + 1) * self.width])
+    }
+
+    /// Each row from the top
+    pub fn rows(&self) -> ChunksExact<'_, T> {
+        self.cells.chunks_exact(self.width.max(1))
+    }
+
+    /// Cells of the column, top to bottom
+    pub fn column(&self, x: usize) -> Option<Column<'_, T>> {
+        (x < self.width).then(|| self.cells.iter().skip(x).step_by(self.width))
+    }
+
+    /// Each column from the left
+    pub fn columns(&self) -> impl Iterator<Item = Column<'_, T>> + '_ {
+        (0..self.width).map(|x| self.cells.iter().skip(x).step_by(self.width))
+    }
+
+    /// (X, Y) of the cells next to (x, y) which are inside the grid, clockwise from above
+    pub fn neighbours(&self, x: usize, y: usize, connectivity: Connectivity) -> impl Iterator<Item = (usize, usize)> + '_ {
+        connectivity.offsets()
+            .iter()
+            .filter_map(move |&(dx, dy)| x.checked_add_signed(dx).zip(y.checked_add_signed(dy)))
+            .filter(|&(nx, ny)| self.contains(nx, ny))
+    }
+
+    /// The `width` by `height` rectangle with its top-left corner at (x, y),
+    /// or None if it doesn't fit in the grid
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<GridView<'_, T>> {
+        let fits = x.checked_add(width).is_some_and(|right| right <= self.width)
+            && y.checked_add(height).is_some_and(|bottom| bottom <= self.height);
+
+        fits.then_some(GridView { grid: self, x, y, width, height })
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /// A grid with every cell set to `value`
+    pub fn filled(width: usize, height: usize, value: T) -> Self {
+        Self { width, height, cells: vec![value; width * height] }
+    }
+
+    /// The cells column by column, as the editor writes them
+    pub fn to_columns(&self) -> Vec<Vec<T>> {
+        self.columns().map(|column| column.cloned().collect()).collect()
+    }
+
+    /// Change the dimensions, keeping the top-left corner and filling new cells with `value`
+    pub fn resize(&mut self, width: usize, height: usize, value: T) {
+        let old = std::mem::replace(self, Self::filled(width, height, value));
+        for (x, y, cell) in old.iter() {
+            if let Some(new) = self.get_mut(x, y) {
+                *new = cell.clone();
+            }
+        }
+    }
+}
+
+impl<T: Clone + Default> Grid<T> {
+    /// A grid of default cells
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::filled(width, height, T::default())
+    }
+}
+
+/// Which cells count as next to each other
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Sharing an edge
+    Four,
+    /// Sharing an edge or a corner
+    Eight,
+}
+
+impl Connectivity {
+    /// (X, Y) offsets of the neighbours, clockwise from above
+    pub fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Self::Four => &[(0, -1), (1, 0), (0, 1), (-1, 0)],
+            Self::Eight => &[(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)],
+        }
+    }
+}
+
+/// Cells of a column of a [`Grid`], top to bottom
+pub type Column<'a, T> = StepBy<Skip<slice::Iter<'a, T>>>;
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        let (w, h) = self.dimensions();
+        self.get(x, y).unwrap_or_else(|| panic!("({x}, {y}) is outside the {w}x{h} grid"))
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        let (w, h) = self.dimensions();
+        self.get_mut(x, y).unwrap_or_else(|| panic!("({x}, {y}) is outside the {w}x{h} grid"))
+    }
+}
+
+/// A rectangle within a [`Grid`], indexed from its own top-left corner
+#[derive(Clone, Copy, Debug)]
+pub struct GridView<'a, T> {
+    grid: &'a Grid<T>,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a, T> GridView<'a, T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// (X, Y) of the view's top-left corner in the grid
+    pub fn origin(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+
+    /// The cell at (x, y) relative to the view, or None if outside it
+    pub fn get(&self, x: usize, y: usize) -> Option<&'a T> {
+        (x < self.width && y < self.height).then(|| &self.grid[(self.x + x, self.y + y)])
+    }
+
+    /// Cells of the row relative to the view, left to right
+    pub fn row(&self, y: usize) -> Option<&'a [T]> {
+        let row = self.grid.row(self.y + y).filter(|_| y < self.height)?;
+        Some(&row[self.x..self.x + self.width])
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [T]> + '_ {
+        (0..self.height).filter_map(|y| self.row(y))
+    }
+
+    /// Every cell as `(x, y, cell)` relative to the view, row by row
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, &'a T)> + '_ {
+        self.rows()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, cell)| (x, y, cell)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connectivity, Grid};
+
+    #[test]
+    fn test_grid() {
+        let mut grid = Grid::from_columns(vec![vec![0, 3], vec![1, 4], vec![2, 5]]).unwrap();
+        assert_eq!(grid.dimensions(), (3, 2));
+        assert_eq!(grid, Grid::from_fn(3, 2, |x, y| y * 3 + x));
+        assert_eq!(grid[(2, 0)], 2);
+        assert_eq!(grid.get(3, 0), None);
+        assert_eq!(grid.row(1), Some(&[3, 4, 5][..]));
+        assert_eq!(grid.column(1).unwrap().copied().collect::<Vec<_>>(), [1, 4]);
+        assert_eq!(grid.to_columns(), [[0, 3], [1, 4], [2, 5]]);
+        assert!(Grid::from_columns(vec![vec![0, 1], vec![2]]).is_none());
+
+        let view = grid.view(1, 1, 2, 1).unwrap();
+        assert_eq!(view.get(1, 0), Some(&5));
+        assert_eq!(view.iter().map(|(x, y, &cell)| (x, y, cell)).collect::<Vec<_>>(), [(0, 0, 4), (1, 0, 5)]);
+        assert!(grid.view(2, 0, 2, 1).is_none());
+
+        assert_eq!(grid.neighbours(0, 1, Connectivity::Four).collect::<Vec<_>>(), [(0, 0), (1, 1)]);
+        assert_eq!(grid.neighbours(1, 1, Connectivity::Eight).count(), 5);
+
+        grid.resize(2, 3, 9);
+        assert_eq!(grid.to_columns(), [[0, 3, 9], [1, 4, 9]]);
+    }
+
+    #[test]
+    fn test_grid_edges() {
+        let empty: Grid<u8> = Grid::from_columns(vec![]).unwrap();
+        assert_eq!(empty.dimensions(), (0, 0));
+        assert_eq!(empty.rows().count(), 0);
+        assert_eq!(empty.columns().count(), 0);
+        assert_eq!(empty.iter().count(), 0);
+
+        // Columns without cells still count towards the width
+        let flat: Grid<u8> = Grid::from_columns(vec![vec![], vec![]]).unwrap();
+        assert_eq!(flat.dimensions(), (2, 0));
+        assert!(flat.column(1).is_some_and(|mut column| column.next().is_none()));
+        assert!(flat.column(2).is_none());
+
+        let grid = Grid::from_fn(2, 2, |x, y| (x, y));
+        assert!(grid.view(2, 2, 0, 0).is_some_and(|view| view.iter().count() == 0));
+        assert!(grid.view(1, 0, usize::MAX, 1).is_none());
+        assert_eq!(grid.neighbours(5, 5, Connectivity::Eight).count(), 0);
+        assert_eq!(grid.neighbours(usize::MAX, 0, Connectivity::Four).count(), 0);
+
+        let mut shrunk = grid.clone();
+        shrunk.resize(0, 0, (9, 9));
+        assert_eq!(shrunk.dimensions(), (0, 0));
+        shrunk.resize(1, 1, (9, 9));
+        assert_eq!(shrunk[(0, 0)], (9, 9));
+    }
+}
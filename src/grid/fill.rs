@@ -0,0 +1,132 @@
+//! Flood fill and labelling of connected groups of cells.
+
+use std::collections::VecDeque;
+
+use super::{Connectivity, Grid};
+
+/// A connected group of cells, see [`Grid::regions`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// Number of cells
+    pub size: usize,
+    /// (X, Y) of the first cell found, scanning row by row
+    pub start: (usize, usize),
+    /// (Left, Top, Right, Bottom) of the bounding box, inclusive
+    pub bounds: (usize, usize, usize, usize),
+}
+
+/// Every region of a grid, with the region each cell belongs to
+#[derive(Clone, Debug, Default)]
+pub struct Regions {
+    /// Index into `regions` of each cell, None for cells which weren't included
+    pub labels: Grid<Option<usize>>,
+    /// In the order their first cell is found, scanning row by row
+    pub regions: Vec<Region>,
+}
+
+impl Regions {
+    /// The region containing (x, y), if any
+    pub fn at(&self, x: usize, y: usize) -> Option<&Region> {
+        self.regions.get((*self.labels.get(x, y)?)?)
+    }
+
+    /// (X, Y) of every cell of the region at `index`, row by row
+    pub fn cells(&self, index: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.labels.iter()
+            .filter(move |(_, _, label)| **label == Some(index))
+            .map(|(x, y, _)| (x, y))
+    }
+}
+
+impl<T> Grid<T> {
+    /// (X, Y) of every cell reachable from (x, y) by stepping between cells
+    /// that `include` accepts, nearest first. Empty if (x, y) isn't included
+    pub fn flood_fill(&self, x: usize, y: usize, connectivity: Connectivity, mut include: impl FnMut(&T) -> bool) -> Vec<(usize, usize)> {
+        let mut seen = Grid::filled(self.width, self.height, false);
+        self.fill_from((x, y), connectivity, &mut include, &mut seen)
+    }
+
+    /// Label the connected groups of cells that `include` accepts
+    pub fn regions(&self, connectivity: Connectivity, mut include: impl FnMut(&T) -> bool) -> Regions {
+        let mut seen = Grid::filled(self.width, self.height, false);
+        let mut labels = Grid::filled(self.width, self.height, None);
+        let mut regions = vec![];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if seen[(x, y)] {
+                    continue;
+                }
+
+                let cells = self.fill_from((x, y), connectivity, &mut include, &mut seen);
+                if cells.is_empty() {
+                    continue;
+                }
+
+                let mut bounds = (x, y, x, y);
+                for &(cx, cy) in &cells {
+                    labels[(cx, cy)] = Some(regions.len());
+                    bounds = (bounds.0.min(cx), bounds.1.min(cy), bounds.2.max(cx), bounds.3.max(cy));
+                }
+                regions.push(Region { size: cells.len(), start: (x, y), bounds });
+            }
+        }
+
+        Regions { labels, regions }
+    }
+
+    /// Breadth first fill, marking every cell looked at in `seen`
+    fn fill_from(
+        &self,
+        start: (usize, usize),
+        connectivity: Connectivity,
+        include: &mut impl FnMut(&T) -> bool,
+        seen: &mut Grid<bool>,
+    ) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        let mut queue = VecDeque::from([start]);
+        if !self.contains(start.0, start.1) {
+            return cells
+        }
+        seen[start] = true;
+
+        while let Some((x, y)) = queue.pop_front() {
+            if !include(&self[(x, y)]) {
+                continue;
+            }
+
+            cells.push((x, y));
+            for next in self.neighbours(x, y, connectivity) {
+                if !seen[next] {
+                    seen[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::{Connectivity, Grid};
+
+    #[test]
+    fn test_regions() {
+        let rows = ["#..#", "#.##", "##.#"];
+        let grid = Grid::from_fn(4, 3, |x, y| rows[y].as_bytes()[x]);
+        let open = |cell: &u8| *cell == b'.';
+
+        assert_eq!(grid.flood_fill(1, 1, Connectivity::Four, open), [(1, 1), (1, 0), (2, 0)]);
+        assert!(grid.flood_fill(0, 0, Connectivity::Four, open).is_empty());
+
+        let four = grid.regions(Connectivity::Four, open);
+        let sizes: Vec<_> = four.regions.iter().map(|r| (r.size, r.bounds)).collect();
+        assert_eq!(sizes, [(3, (1, 0, 2, 1)), (1, (2, 2, 2, 2))]);
+        assert_eq!(four.at(2, 2).map(|r| r.start), Some((2, 2)));
+        assert_eq!(four.cells(0).collect::<Vec<_>>(), [(1, 0), (2, 0), (1, 1)]);
+
+        assert_eq!(grid.regions(Connectivity::Eight, open).regions.len(), 1);
+    }
+}
@@ -1,5 +1,22 @@
 pub mod rwlevel;
+pub mod compiled;
+pub mod shortcuts;
+pub mod lint;
+pub mod render;
+pub mod schema;
+pub mod diff;
+pub mod patch;
+pub mod merge;
+pub mod fingerprint;
+pub mod batch;
+pub mod cache;
+pub mod movement;
+mod grid;
 mod tile;
 mod effect;
 
-pub use rwlevel::RWLevel;
\ No newline at end of file
+pub use rwlevel::{RWLevel, RWLevelMeta, Section, Sections};
+pub use rwlevel::lingo_dsl::Point;
+pub use tile::{Feature, FeatureIter, FeatureSet, Geometry, Tile, TileLayer, TileMut};
+pub use effect::Effect;
+pub use grid::{Column, Connectivity, Grid, GridView, Region, Regions};
\ No newline at end of file
@@ -0,0 +1,464 @@
+//! Checks levels for common mistakes which the editor allows but the game
+//! either rejects or handles badly.
+
+mod rules;
+mod config;
+pub mod report;
+
+use std::fmt;
+
+use crate::RWLevel;
+
+pub use config::{LintConfig, RuleSetting};
+pub use rules::RULES;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A built-in lint rule
+#[derive(Copy, Clone)]
+pub struct Rule {
+    /// Stable identifier, e.g. `RW001`. Never reused once assigned
+    pub id: &'static str,
+    /// Short kebab-case name
+    pub name: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+    /// Whether the rule runs when the config doesn't mention it
+    pub enabled_by_default: bool,
+    check: fn(&Rule, &RWLevel) -> Vec<Diagnostic>,
+    /// Only present for rules whose diagnostics have a single obvious correction
+    fix: Option<fn(&Rule, &mut RWLevel) -> Vec<Fix>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// ID of the rule which produced this diagnostic
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// Layer index (see [RWLevel::L1_FG]) the problem is on, if it's tied to one
+    pub layer: Option<usize>,
+    /// (X, Y) of the offending tile, if it's tied to one
+    pub pos: Option<(usize, usize)>,
+    pub message: String,
+}
+
+/// A change made to a level by [RWLevel::fix]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fix {
+    /// ID of the rule whose diagnostic was fixed
+    pub rule: &'static str,
+    pub layer: usize,
+    pub pos: (usize, usize),
+    pub change: String,
+}
+
+/// A check over a whole level. Implement this for project-specific house
+/// rules and add them to a [Linter] next to the built-in [RULES]
+pub trait LintRule {
+    /// Stable identifier. Built-in rules use `RW` followed by a number, so
+    /// custom rules should pick a different prefix
+    fn id(&self) -> &'static str;
+
+    /// Short kebab-case name, usable in place of the ID in a [LintConfig]
+    fn name(&self) -> &'static str;
+
+    /// Severity used when the config doesn't override it
+    fn severity(&self) -> Severity;
+
+    fn description(&self) -> &'static str {
+        ""
+    }
+
+    /// Rules which are disabled by default only run when the config turns them on
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn check(&self, level: &RWLevel) -> Vec<Diagnostic>;
+
+    fn is_fixable(&self) -> bool {
+        false
+    }
+
+    /// Correct every diagnostic of this rule. Only called if [LintRule::is_fixable]
+    fn fix(&self, _level: &mut RWLevel) -> Vec<Fix> {
+        vec![]
+    }
+}
+
+/// A set of rules to run, along with their configuration
+pub struct Linter {
+    rules: Vec<Box<dyn LintRule>>,
+    config: LintConfig,
+}
+
+impl LintRule for Rule {
+    fn id(&self) -> &'static str {
+        self.id
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        self.enabled_by_default
+    }
+
+    fn check(&self, level: &RWLevel) -> Vec<Diagnostic> {
+        (self.check)(self, level)
+    }
+
+    fn is_fixable(&self) -> bool {
+        self.fix.is_some()
+    }
+
+    fn fix(&self, level: &mut RWLevel) -> Vec<Fix> {
+        self.fix.map(|fix| fix(self, level)).unwrap_or_default()
+    }
+}
+
+impl Rule {
+    fn fixed(&self, layer: usize, pos: (usize, usize), change: impl Into<String>) -> Fix {
+        Fix::new(self, layer, pos, change)
+    }
+
+    fn at(&self, layer: usize, pos: (usize, usize), message: impl Into<String>) -> Diagnostic {
+        Diagnostic::at(self, layer, pos, message)
+    }
+
+    fn global(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::global(self, message)
+    }
+}
+
+impl Diagnostic {
+    /// Diagnostic for a single tile, using the rule's default severity
+    pub fn at(rule: &dyn LintRule, layer: usize, pos: (usize, usize), message: impl Into<String>) -> Self {
+        Self {
+            rule: rule.id(),
+            severity: rule.severity(),
+            layer: Some(layer),
+            pos: Some(pos),
+            message: message.into(),
+        }
+    }
+
+    /// Diagnostic for the level as a whole, using the rule's default severity
+    pub fn global(rule: &dyn LintRule, message: impl Into<String>) -> Self {
+        Self {
+            rule: rule.id(),
+            severity: rule.severity(),
+            layer: None,
+            pos: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl Fix {
+    pub fn new(rule: &dyn LintRule, layer: usize, pos: (usize, usize), change: impl Into<String>) -> Self {
+        Self {
+            rule: rule.id(),
+            layer,
+            pos,
+            change: change.into(),
+        }
+    }
+}
+
+impl Linter {
+    /// A linter with every built-in rule and the default configuration
+    pub fn new() -> Self {
+        Self {
+            rules: RULES.iter().map(|rule| Box::new(*rule) as Box<dyn LintRule>).collect(),
+            config: LintConfig::default(),
+        }
+    }
+
+    /// Add a custom rule, which runs after every rule registered before it
+    pub fn register<R: LintRule + 'static>(&mut self, rule: R) -> &mut Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn set_config(&mut self, config: LintConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &LintConfig {
+        &self.config
+    }
+
+    /// Every registered rule, including disabled ones
+    pub fn rules(&self) -> impl Iterator<Item = &dyn LintRule> {
+        self.rules.iter().map(Box::as_ref)
+    }
+
+    /// Registered rules which the config leaves enabled
+    pub fn enabled_rules(&self) -> impl Iterator<Item = &dyn LintRule> {
+        self.rules().filter(|rule| self.config.severity_of(*rule).is_some())
+    }
+
+    /// Names and IDs in the config which aren't those of a registered rule
+    pub fn unknown_config_rules(&self) -> impl Iterator<Item = &str> {
+        self.config.rules().filter(|name| !self.rules().any(|rule| rule.id() == *name || rule.name() == *name))
+    }
+
+    /// Run every enabled rule. Diagnostics keep the severity their rule gave
+    /// them unless the config sets one for the rule
+    pub fn check(&self, level: &RWLevel) -> Vec<Diagnostic> {
+        self.enabled_rules()
+            .flat_map(|rule| {
+                let severity = match self.config.setting(rule) {
+                    Some(RuleSetting::Severity(severity)) => Some(severity),
+                    _ => None,
+                };
+                rule.check(level)
+                    .into_iter()
+                    .map(move |mut diagnostic| {
+                        diagnostic.severity = severity.unwrap_or(diagnostic.severity);
+                        diagnostic
+                    })
+            })
+            .collect()
+    }
+
+    /// Apply the fix of every enabled rule which has one, reporting each change made
+    pub fn fix(&self, level: &mut RWLevel) -> Vec<Fix> {
+        self.enabled_rules()
+            .filter(|rule| rule.is_fixable())
+            .flat_map(|rule| rule.fix(level))
+            .collect()
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RWLevel {
+    /// Run every built-in rule over the level
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        Linter::new().check(self)
+    }
+
+    /// Apply the fix of every built-in rule which has one, reporting each change made
+    pub fn fix(&mut self) -> Vec<Fix> {
+        Linter::new().fix(self)
+    }
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (x, y) = self.pos;
+        write!(f, "fixed[{}] ({x}, {y}) L{}: {}", self.rule, self.layer + 1, self.change)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]", self.severity, self.rule)?;
+        if let Some((x, y)) = self.pos {
+            write!(f, " ({x}, {y})")?;
+        }
+        if let Some(layer) = self.layer {
+            write!(f, " L{}", layer + 1)?;
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rwlevel::fixture::{self, Edit}, Feature, RWLevel};
+
+    use super::{Diagnostic, LintConfig, LintRule, Linter, Severity};
+
+    #[test]
+    fn test_clean_level() {
+        let level = fixture::level(&[
+            "#######",
+            "E>...>D",
+            "##...##",
+            "#######",
+        ]);
+
+        let diagnostics = level.lint();
+        assert!(diagnostics.is_empty(), "{diagnostics:#?}");
+    }
+
+    #[test]
+    fn test_rule_ids() {
+        let level = fixture::level(&[
+            "o####",
+            "#....",
+            "#.>..",
+            "#..|I",
+            "###D#",
+        ]);
+
+        let mut ids: Vec<&str> = level.lint().iter().map(|d| d.rule).collect();
+        ids.dedup();
+        assert_eq!(ids, ["RW001", "RW003", "RW004", "RW006", "RW007"]);
+    }
+
+    #[test]
+    fn test_bad_effect_matrix() {
+        // A short column and a string cell are read as 0, rather than failing the load
+        let project = fixture::project(&["#.#"])
+            .edit("#effects: []", r#"#effects: [[#nm: "Slime", #mtrx: [[0], [], ["5"]], #Options: []]]"#);
+        let level = RWLevel::from_project_str("TEST", &project).unwrap();
+        assert_eq!(level.effects()[0].matrix().dimensions(), (3, 1));
+
+        let messages: Vec<String> = level.lint().iter().map(|d| format!("{} {}", d.rule, d.message)).collect();
+        assert_eq!(messages, ["RW008 effect Slime has 2 missing or non-numeric cells, read as 0"]);
+    }
+
+    #[test]
+    fn test_sealed_pocket() {
+        let level = fixture::level(&[
+            "#######",
+            "E>..#.#",
+            "#######",
+        ]);
+
+        // Only reported when turned on
+        assert!(level.lint().is_empty());
+
+        let mut linter = Linter::new();
+        linter.set_config(LintConfig::parse("sealed-pocket = on").unwrap());
+        let diagnostics = linter.check(&level);
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:#?}");
+        assert_eq!((diagnostics[0].rule, diagnostics[0].pos), ("RW010", Some((5, 1))));
+
+        // The edge of the room walls a pocket off like any other wall
+        let level = fixture::level(&[
+            "######",
+            "E>..#.",
+            "#####.",
+        ]);
+        let diagnostics = linter.check(&level);
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:#?}");
+        assert_eq!(diagnostics[0].pos, Some((5, 1)));
+        assert_eq!(diagnostics[0].message, "2 open tiles can't be reached from any shortcut");
+    }
+
+    #[test]
+    fn test_fix() {
+        let project = fixture::project(&[
+            "o####",
+            "#I+.#",
+            "#####",
+        ]);
+        // Duplicate the VPole on the horizontal + vertical pole tile, listing it first
+        let project = project.replacen("[0, [1, 2]]", "[0, [2, 1, 2]]", 1);
+        let mut level = RWLevel::from_project_str("TEST", &project).unwrap();
+
+        let fixes = level.fix();
+        let fixed: Vec<(&str, (usize, usize))> = fixes.iter().map(|fix| (fix.rule, fix.pos)).collect();
+        assert_eq!(fixed, [("RW003", (0, 0)), ("RW006", (1, 1)), ("RW009", (2, 1))]);
+
+        assert!(level.lint().is_empty());
+        assert!(level.fix().is_empty());
+        assert_eq!(level.tile(RWLevel::L1_FG, 2, 1).unwrap().features().iter().collect::<Vec<_>>(), [Feature::HPole, Feature::VPole]);
+
+        // The order the features were listed in is kept
+        let saved = level.to_project_string();
+        assert!(saved.contains("[0, [2, 1]]"), "{saved}");
+        let saved = RWLevel::from_project_str("TEST", &saved).unwrap();
+        assert!(saved.lint().is_empty());
+    }
+
+    struct MaxCameras(usize);
+
+    impl LintRule for MaxCameras {
+        fn id(&self) -> &'static str {
+            "HOUSE001"
+        }
+
+        fn name(&self) -> &'static str {
+            "max-cameras"
+        }
+
+        fn severity(&self) -> Severity {
+            Severity::Warning
+        }
+
+        fn check(&self, level: &RWLevel) -> Vec<Diagnostic> {
+            let cameras = level.meta().cameras().len();
+            if cameras <= self.0 {
+                return vec![]
+            }
+
+            // Far too many cameras is an error rather than the rule's usual warning
+            let mut diagnostic = Diagnostic::global(self, format!("{cameras} cameras, at most {} are allowed", self.0));
+            if cameras > 2 * self.0 {
+                diagnostic.severity = Severity::Error;
+            }
+            vec![diagnostic]
+        }
+    }
+
+    #[test]
+    fn test_custom_rule() {
+        let level = fixture::level(&[
+            "o##",
+            "###",
+        ]);
+
+        let mut linter = Linter::new();
+        linter.register(MaxCameras(0));
+        let ids: Vec<&str> = linter.check(&level).iter().map(|d| d.rule).collect();
+        assert_eq!(ids, ["RW003", "HOUSE001"]);
+
+        let config = LintConfig::parse("# house rules\nRW003 = off\nmax-cameras = error\n").unwrap();
+        linter.set_config(config);
+        let diagnostics = linter.check(&level);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].rule, diagnostics[0].severity), ("HOUSE001", Severity::Error));
+        assert!(linter.fix(&mut fixture::level(&["o##"])).is_empty());
+        assert_eq!(linter.unknown_config_rules().count(), 0);
+
+        // Turning a rule on keeps the severities it reports with
+        linter.set_config(LintConfig::parse("max-cameras = on\nRW999 = off\n").unwrap());
+        let severities: Vec<Severity> = linter.check(&level).iter().map(|d| d.severity).collect();
+        assert_eq!(severities, [Severity::Warning, Severity::Error]);
+        assert_eq!(linter.unknown_config_rules().collect::<Vec<_>>(), ["RW999"]);
+
+        assert!(LintConfig::parse("RW003 = sometimes").is_err());
+    }
+}
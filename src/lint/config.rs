@@ -0,0 +1,90 @@
+//! Per-project rule configuration, read from a file of `rule = setting` lines:
+//!
+//! ```text
+//! # Orphan path dots are used as decoration in this mod
+//! RW003 = off
+//! room-exit-border = error
+//! max-cameras = on
+//! ```
+//!
+//! Rules may be named by ID or by name. Settings are `off`, `on` (default
+//! severity), or one of the severities `info`, `warning` and `error`.
+
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use super::{LintRule, Severity};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RuleSetting {
+    Off,
+    On,
+    Severity(Severity),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LintConfig {
+    /// Keyed by whatever the config file used to name the rule
+    settings: HashMap<String, RuleSetting>,
+}
+
+impl LintConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let contents = read_to_string(&path)
+            .map_err(|e| format!("{}: {e}", path.as_ref().display()))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (rule, setting) = line.split_once('=')
+                .ok_or_else(|| format!("line {}: expected `rule = setting`", idx + 1))?;
+            let setting = match setting.trim().to_ascii_lowercase().as_str() {
+                "off" => RuleSetting::Off,
+                "on" => RuleSetting::On,
+                "info" => RuleSetting::Severity(Severity::Info),
+                "warning" => RuleSetting::Severity(Severity::Warning),
+                "error" => RuleSetting::Severity(Severity::Error),
+                other => return Err(format!("line {}: unknown setting `{other}`", idx + 1)),
+            };
+
+            config.set(rule.trim(), setting);
+        }
+
+        Ok(config)
+    }
+
+    /// Override a rule, named by either its ID or its name
+    pub fn set(&mut self, rule: &str, setting: RuleSetting) -> &mut Self {
+        self.settings.insert(rule.to_owned(), setting);
+        self
+    }
+
+    /// The setting for a rule, if the config mentions it. IDs take precedence over names
+    pub fn setting(&self, rule: &dyn LintRule) -> Option<RuleSetting> {
+        self.settings.get(rule.id())
+            .or_else(|| self.settings.get(rule.name()))
+            .copied()
+    }
+
+    /// Severity the rule should report with, or None if it's disabled
+    pub fn severity_of(&self, rule: &dyn LintRule) -> Option<Severity> {
+        match self.setting(rule) {
+            Some(RuleSetting::Off) => None,
+            Some(RuleSetting::On) => Some(rule.severity()),
+            Some(RuleSetting::Severity(severity)) => Some(severity),
+            None => rule.enabled_by_default().then(|| rule.severity()),
+        }
+    }
+
+    /// Rule names and IDs mentioned in the config, for reporting typos
+    pub fn rules(&self) -> impl Iterator<Item = &str> {
+        self.settings.keys().map(String::as_str)
+    }
+}
@@ -0,0 +1,143 @@
+//! Machine-readable output of lint results, for CI and code review tools.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use super::{Diagnostic, Linter, Severity};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+impl Severity {
+    /// SARIF's name for the severity
+    fn sarif_level(&self) -> &'static str {
+        match self {
+            Self::Info => "note",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// Forward slashes regardless of platform, as both formats expect URIs
+fn uri(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// One flat array with an object per diagnostic. `layer` is 1-based as in the
+/// editor, and `layer`, `x` and `y` are null for diagnostics of the whole level
+pub fn to_json(files: &[(PathBuf, Vec<Diagnostic>)]) -> Value {
+    files.iter()
+        .flat_map(|(path, diagnostics)| diagnostics.iter().map(move |d| json!({
+            "file": uri(path),
+            "rule": d.rule,
+            "severity": d.severity.as_str(),
+            "layer": d.layer.map(|layer| layer + 1),
+            "x": d.pos.map(|(x, _)| x),
+            "y": d.pos.map(|(_, y)| y),
+            "message": d.message,
+        })))
+        .collect()
+}
+
+/// A SARIF 2.1.0 log with a single run, describing the enabled rules of the
+/// linter. Project files keep all of the geometry on one line, so every result
+/// points at line 1 and carries the tile coordinates as a logical location and
+/// in its properties
+pub fn to_sarif(linter: &Linter, files: &[(PathBuf, Vec<Diagnostic>)]) -> Value {
+    let rules: Vec<Value> = linter.enabled_rules()
+        .map(|rule| {
+            let severity = linter.config().severity_of(rule).unwrap_or(rule.severity());
+            json!({
+                "id": rule.id(),
+                "name": rule.name(),
+                "shortDescription": { "text": rule.description() },
+                "defaultConfiguration": { "level": severity.sarif_level() },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = files.iter()
+        .flat_map(|(path, diagnostics)| diagnostics.iter().map(move |d| sarif_result(path, d)))
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn sarif_result(path: &Path, diagnostic: &Diagnostic) -> Value {
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": uri(path) },
+            "region": { "startLine": 1 },
+        }
+    });
+
+    if let Some((x, y)) = diagnostic.pos {
+        let layer = diagnostic.layer.map_or(String::new(), |layer| format!(" L{}", layer + 1));
+        location["logicalLocations"] = json!([{
+            "name": format!("tile({x}, {y}){layer}"),
+            "kind": "element",
+        }]);
+    }
+
+    let message = match diagnostic.pos {
+        Some((x, y)) => format!("({x}, {y}): {}", diagnostic.message),
+        None => diagnostic.message.clone(),
+    };
+
+    json!({
+        "ruleId": diagnostic.rule,
+        "level": diagnostic.severity.sarif_level(),
+        "message": { "text": message },
+        "locations": [location],
+        "properties": {
+            "layer": diagnostic.layer.map(|layer| layer + 1),
+            "x": diagnostic.pos.map(|(x, _)| x),
+            "y": diagnostic.pos.map(|(_, y)| y),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::{lint::Linter, rwlevel::fixture};
+
+    use super::{to_json, to_sarif};
+
+    #[test]
+    fn test_formats() {
+        let level = fixture::level(&[
+            "o##",
+            "###",
+        ]);
+        let files = vec![(PathBuf::from("LevelEditorProjects/SU/SU_A01.txt"), level.lint())];
+
+        let json = to_json(&files);
+        assert_eq!(json[0]["rule"], "RW003");
+        assert_eq!(json[0]["layer"], 1);
+        assert_eq!(json[0]["x"], 0);
+
+        let sarif = to_sarif(&Linter::new(), &files);
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(result["ruleId"], "RW003");
+        assert_eq!(result["level"], "warning");
+        assert_eq!(result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "LevelEditorProjects/SU/SU_A01.txt");
+        assert_eq!(result["locations"][0]["logicalLocations"][0]["name"], "tile(0, 0) L1");
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{shortcuts::ShortcutEnd, Connectivity, Feature, Geometry, RWLevel};
+
+use super::{Diagnostic, Fix, Rule, Severity};
+
+pub static RULES: &[Rule] = &[
+    Rule {
+        id: "RW001",
+        name: "shortcut-entrance-enclosure",
+        severity: Severity::Error,
+        description: "Shortcut entrances must be surrounded by wall on three sides",
+        enabled_by_default: true,
+        check: entrance_enclosure,
+        fix: None,
+    },
+    Rule {
+        id: "RW002",
+        name: "shortcut-entrance-mismatch",
+        severity: Severity::Error,
+        description: "Shortcut entrance geometry and the shortcut entrance feature must appear together",
+        enabled_by_default: true,
+        check: entrance_mismatch,
+        fix: Some(fix_entrance_mismatch),
+    },
+    Rule {
+        id: "RW003",
+        name: "orphan-path-dot",
+        severity: Severity::Warning,
+        description: "Shortcut path dots must be part of a shortcut",
+        enabled_by_default: true,
+        check: orphan_path_dots,
+        fix: Some(fix_orphan_path_dots),
+    },
+    Rule {
+        id: "RW004",
+        name: "broken-shortcut",
+        severity: Severity::Error,
+        description: "Shortcuts must lead somewhere without branching or looping",
+        enabled_by_default: true,
+        check: broken_shortcuts,
+        fix: None,
+    },
+    Rule {
+        id: "RW005",
+        name: "room-exit-border",
+        severity: Severity::Warning,
+        description: "Room exits must sit in the border of the level",
+        enabled_by_default: true,
+        check: room_exit_border,
+        fix: None,
+    },
+    Rule {
+        id: "RW006",
+        name: "pole-in-wall",
+        severity: Severity::Warning,
+        description: "Poles inside solid tiles can't be climbed",
+        enabled_by_default: true,
+        check: pole_in_wall,
+        fix: Some(fix_pole_in_wall),
+    },
+    Rule {
+        id: "RW007",
+        name: "den-without-shortcut",
+        severity: Severity::Error,
+        description: "Creature dens must be reachable through a shortcut",
+        enabled_by_default: true,
+        check: den_without_shortcut,
+        fix: None,
+    },
+    Rule {
+        id: "RW008",
+        name: "effect-matrix-size",
+        severity: Severity::Error,
+        description: "Effect matrices must be rectangles of numbers matching the dimensions of the level",
+        enabled_by_default: true,
+        check: effect_matrix_size,
+        fix: None,
+    },
+    Rule {
+        id: "RW009",
+        name: "duplicate-feature",
+        severity: Severity::Warning,
+        description: "A feature should only appear once per tile",
+        enabled_by_default: true,
+        check: duplicate_features,
+        fix: Some(fix_duplicate_features),
+    },
+    Rule {
+        id: "RW010",
+        name: "sealed-pocket",
+        severity: Severity::Info,
+        description: "Open areas of layer 1 should be reachable through a shortcut. \
+            Any tile that isn't wall or glass counts as open, and leads to the open tiles \
+            above, below and beside it regardless of jumps, poles or the other layers",
+        enabled_by_default: false,
+        check: sealed_pockets,
+        fix: None,
+    },
+    Rule {
+        id: "RW011",
+        name: "soft-lock",
+        severity: Severity::Info,
+        description: "The player shouldn't be able to get somewhere no room exit can be reached from",
+        enabled_by_default: true,
+        check: soft_locks,
+        fix: None,
+    },
+];
+
+/// Every (x, y) of the level, column by column
+fn positions(level: &RWLevel) -> impl Iterator<Item = (usize, usize)> {
+    let (w, h) = level.meta().dimensions();
+    (0..w).flat_map(move |x| (0..h).map(move |y| (x, y)))
+}
+
+fn entrance_enclosure(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    positions(level)
+        .filter(|&(x, y)| level.tile(RWLevel::L1_FG, x, y).is_some_and(|t| t.is_shortcut_entrance()))
+        .filter_map(|(x, y)| {
+            // Anything outside of the level counts as wall
+            let open = level.neighbours(RWLevel::L1_FG, x, y, Connectivity::Four)
+                .filter(|(_, _, t)| !t.geometry.is_solid())
+                .count();
+            let solid = 4 - open;
+
+            (solid < 3).then(|| rule.at(RWLevel::L1_FG, (x, y), format!("shortcut entrance has wall on {solid} sides")))
+        })
+        .collect()
+}
+
+fn entrance_mismatch(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    positions(level)
+        .filter_map(|(x, y)| {
+            let tile = level.tile(RWLevel::L1_FG, x, y)?;
+            let geometry = tile.geometry == Geometry::ShortcutEntrance;
+            let feature = tile.features().contains(&Feature::ShortcutEntrance);
+
+            match (geometry, feature) {
+                (true, false) => Some(rule.at(RWLevel::L1_FG, (x, y), "shortcut entrance geometry without the shortcut entrance feature")),
+                (false, true) => Some(rule.at(RWLevel::L1_FG, (x, y), "shortcut entrance feature without the shortcut entrance geometry")),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Only geometry without the feature is fixed. A lone feature could either
+/// be a leftover or be missing its geometry, so it's left for a human
+fn fix_entrance_mismatch(rule: &Rule, level: &mut RWLevel) -> Vec<Fix> {
+    positions(level)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .filter_map(|(x, y)| {
+            let mut tile = level.tile_mut(RWLevel::L1_FG, x, y)?;
+            if tile.geometry != Geometry::ShortcutEntrance || tile.features().contains(&Feature::ShortcutEntrance) {
+                return None
+            }
+
+            tile.add_features(&[Feature::ShortcutEntrance]);
+            Some(rule.fixed(RWLevel::L1_FG, (x, y), "added the shortcut entrance feature"))
+        })
+        .collect()
+}
+
+fn orphan_path_dots(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    level.shortcuts()
+        .orphans
+        .into_iter()
+        .map(|pos| rule.at(RWLevel::L1_FG, pos, "path dot is not connected to a shortcut entrance"))
+        .collect()
+}
+
+fn fix_orphan_path_dots(rule: &Rule, level: &mut RWLevel) -> Vec<Fix> {
+    level.shortcuts()
+        .orphans
+        .into_iter()
+        .filter_map(|(x, y)| {
+            level.tile_mut(RWLevel::L1_FG, x, y)?.remove_features(&[Feature::ShortcutPathDot]);
+            Some(rule.fixed(RWLevel::L1_FG, (x, y), "removed the path dot"))
+        })
+        .collect()
+}
+
+fn broken_shortcuts(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    level.shortcuts()
+        .broken()
+        .map(|shortcut| {
+            let (ex, ey) = shortcut.entrance;
+            let message = match shortcut.end {
+                ShortcutEnd::Dangling(_) => format!("shortcut from ({ex}, {ey}) stops without leading anywhere"),
+                ShortcutEnd::Branch(_) => format!("shortcut from ({ex}, {ey}) branches"),
+                _ => format!("shortcut from ({ex}, {ey}) loops back on itself"),
+            };
+
+            rule.at(RWLevel::L1_FG, shortcut.end.pos(), message)
+        })
+        .collect()
+}
+
+fn room_exit_border(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    let (w, h) = level.meta().dimensions();
+    let (left, top, right, bottom) = level.meta().extra_tiles();
+
+    positions(level)
+        .filter(|&(x, y)| level.tile(RWLevel::L1_FG, x, y).is_some_and(|t| t.features().contains(&Feature::RoomEntrance)))
+        .filter(|&(x, y)| {
+            let in_border = x <= left || y <= top || x + right + 1 >= w || y + bottom + 1 >= h;
+            !in_border
+        })
+        .map(|pos| rule.at(RWLevel::L1_FG, pos, "room exit is inside the playable area"))
+        .collect()
+}
+
+/// (Layer, (X, Y)) of every pole inside a solid tile
+fn embedded_poles(level: &RWLevel) -> Vec<(usize, (usize, usize))> {
+    [RWLevel::L1_FG, RWLevel::L2_MG, RWLevel::L3_BG]
+        .into_iter()
+        .flat_map(|layer| positions(level).map(move |pos| (layer, pos)))
+        .filter(|&(layer, (x, y))| level.tile(layer, x, y).is_some_and(|t| {
+            t.geometry.is_solid() && t.features().iter().any(|f| matches!(f, Feature::HPole | Feature::VPole))
+        }))
+        .collect()
+}
+
+fn pole_in_wall(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    embedded_poles(level)
+        .into_iter()
+        .map(|(layer, pos)| rule.at(layer, pos, "pole is inside a solid tile"))
+        .collect()
+}
+
+fn fix_pole_in_wall(rule: &Rule, level: &mut RWLevel) -> Vec<Fix> {
+    embedded_poles(level)
+        .into_iter()
+        .filter_map(|(layer, (x, y))| {
+            level.tile_mut(layer, x, y)?.remove_features(&[Feature::HPole, Feature::VPole]);
+            Some(rule.fixed(layer, (x, y), "removed the pole"))
+        })
+        .collect()
+}
+
+fn den_without_shortcut(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    let network = level.shortcuts();
+
+    positions(level)
+        .filter(|&(x, y)| level.tile(RWLevel::L1_FG, x, y).is_some_and(|t| t.features().contains(&Feature::CreatureDen)))
+        .filter(|pos| !network.shortcuts.iter().any(|s| s.end == ShortcutEnd::CreatureDen(*pos)))
+        .map(|pos| rule.at(RWLevel::L1_FG, pos, "no shortcut leads to this den"))
+        .collect()
+}
+
+fn effect_matrix_size(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    let (w, h) = level.meta().dimensions();
+
+    let mut diagnostics = vec![];
+    for effect in level.effects() {
+        let (mw, mh) = effect.matrix().dimensions();
+        if (mw, mh) != (w, h) {
+            diagnostics.push(rule.global(format!("effect {} is {mw}x{mh}, but the level is {w}x{h}", effect.name())));
+        }
+        if effect.bad_cells > 0 {
+            diagnostics.push(rule.global(format!("effect {} has {} missing or non-numeric cells, read as 0", effect.name(), effect.bad_cells)));
+        }
+    }
+
+    diagnostics
+}
+
+/// Levels without any shortcut entrance are left alone, as there's no way in to compare against.
+/// As every open tile is taken to lead to its neighbours, this only finds areas walled off
+/// completely, not ones a creature couldn't climb to
+fn sealed_pockets(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    let regions = level.open_regions(RWLevel::L1_FG, Connectivity::Four);
+    let mut entered = vec![false; regions.regions.len()];
+    for (x, y, _) in level.tiles(RWLevel::L1_FG).filter(|(_, _, t)| t.is_shortcut_entrance()) {
+        if let Some(&Some(region)) = regions.labels.get(x, y) {
+            entered[region] = true;
+        }
+    }
+
+    if !entered.contains(&true) {
+        return vec![]
+    }
+
+    regions.regions
+        .iter()
+        .zip(entered)
+        .filter(|(_, entered)| !entered)
+        .map(|(region, _)| rule.at(RWLevel::L1_FG, region.start, format!("{} open tiles can't be reached from any shortcut", region.size)))
+        .collect()
+}
+
+fn soft_locks(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    level.slugcat_reachability()
+        .stuck
+        .into_iter()
+        .map(|(exit, pos)| rule.at(RWLevel::L1_FG, pos, format!("entering from exit {exit}, the player can get stuck here")))
+        .collect()
+}
+
+fn duplicate_features(rule: &Rule, level: &RWLevel) -> Vec<Diagnostic> {
+    level.duplicate_features()
+        .into_iter()
+        .map(|(layer, pos)| rule.at(layer, pos, "tile lists the same feature more than once"))
+        .collect()
+}
+
+/// Saving keeps the order features were listed in, so the repeats stay until removed
+fn fix_duplicate_features(rule: &Rule, level: &mut RWLevel) -> Vec<Fix> {
+    let fixes = level.duplicate_features()
+        .into_iter()
+        .map(|(layer, pos)| rule.fixed(layer, pos, "removed duplicate features"))
+        .collect();
+    level.dedup_features();
+    fixes
+}
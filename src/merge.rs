@@ -0,0 +1,394 @@
+//! Three-way merges of a level edited separately by two people.
+//!
+//! Every tile, cell of placed tiles, effect, prop, camera and setting is merged
+//! on its own: a change made on one side only is kept, and a change made
+//! differently on both sides is a conflict, resolved to our side.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{
+    diff::{effect_settings, longest_increasing, match_effects, match_props, props, settings_line, settings_line_mut, TileDesc, SETTINGS},
+    rwlevel::canonical::is_editor_state,
+    Effect, RWLevel, Tile,
+};
+
+/// A part of the level changed differently by both sides
+#[derive(Clone, Debug, PartialEq)]
+pub enum MergeConflict {
+    Dimensions { ours: (usize, usize), theirs: (usize, usize) },
+    Tile { layer: usize, x: usize, y: usize, ours: Tile, theirs: Tile },
+    TileArt { layer: usize, x: usize, y: usize },
+    Effect { name: String, reason: String },
+    Prop { name: String, reason: String },
+    Camera { index: usize, reason: String },
+    /// Both sides reordered a list differently
+    Order { list: &'static str },
+    Setting { line: &'static str, key: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct Merge {
+    level: RWLevel,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Merge {
+    /// The merged level, holding our side of every conflict
+    pub fn level(&self) -> &RWLevel {
+        &self.level
+    }
+
+    pub fn into_level(self) -> RWLevel {
+        self.level
+    }
+
+    pub fn conflicts(&self) -> &[MergeConflict] {
+        &self.conflicts
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// The value changed by either side, or None if both changed it differently
+fn pick<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || theirs == base {
+        Some(ours.clone())
+    } else if ours == base {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+/// Merge `ours` and `theirs`, both edited from `base`, or None if the merged
+/// settings or effects can't be read back
+pub fn merge(base: &RWLevel, ours: &RWLevel, theirs: &RWLevel) -> Option<Merge> {
+    let mut conflicts = vec![];
+
+    let (b, o, t) = (base.meta().dimensions(), ours.meta().dimensions(), theirs.meta().dimensions());
+    let (w, h) = pick(&b, &o, &t).unwrap_or_else(|| {
+        conflicts.push(MergeConflict::Dimensions { ours: o, theirs: t });
+        o
+    });
+    let resized = |level: &RWLevel| {
+        let mut level = level.clone();
+        if level.meta().dimensions() != (w, h) {
+            level.resize(w, h);
+        }
+        level
+    };
+    let (base, ours, theirs) = (resized(base), resized(ours), resized(theirs));
+    let projects = [base.to_project(), ours.to_project(), theirs.to_project()];
+    let mut project = projects[1].clone();
+
+    for layer in 0..3 {
+        for y in 0..h {
+            for x in 0..w {
+                let tiles = [&base, &ours, &theirs].map(|level| level.tile(layer, x, y).unwrap_or_default());
+                let tile = pick(&tiles[0], &tiles[1], &tiles[2]).unwrap_or_else(|| {
+                    conflicts.push(MergeConflict::Tile { layer, x, y, ours: tiles[1], theirs: tiles[2] });
+                    tiles[1]
+                });
+                // Unchanged tiles keep the order ours listed their features in
+                if tile != tiles[1] {
+                    project._geom[x][y][layer] = tile.to_json();
+                }
+            }
+        }
+    }
+
+    if let Some(Value::Array(columns)) = project._tiles.get_mut("#tlMatrix") {
+        for (x, column) in columns.iter_mut().enumerate().take(w) {
+            for y in 0..h {
+                for layer in 0..3 {
+                    let cells = projects.each_ref().map(|p| p._tiles.get("#tlMatrix")
+                        .and_then(|m| m.get(x)?.get(y)?.get(layer))
+                        .cloned()
+                        .unwrap_or(Value::Null));
+                    // Our matrix may be missing the cell if it's malformed
+                    let target = column.get_mut(y).and_then(|cells| cells.get_mut(layer));
+                    match (pick(&cells[0], &cells[1], &cells[2]), target) {
+                        (Some(cell), Some(target)) => *target = cell,
+                        _ => conflicts.push(MergeConflict::TileArt { layer, x, y }),
+                    }
+                }
+            }
+        }
+    }
+
+    let effects = merge_list(base.effects(), ours.effects(), theirs.effects(), match_effects, merge_effect);
+    for (index, reason) in effects.conflicts {
+        conflicts.push(MergeConflict::Effect { name: base.effects()[index].name().to_owned(), reason });
+    }
+    if effects.reordered {
+        conflicts.push(MergeConflict::Order { list: "effects" });
+    }
+    project._effects["#effects"] = effects.items.iter().map(Effect::to_json).collect();
+
+    let [base_props, our_props, their_props] = projects.each_ref().map(props);
+    let merged = merge_list(&base_props, &our_props, &their_props, match_props, |b, o, t| {
+        pick(b, o, t).ok_or_else(|| "changed on both sides".into())
+    });
+    for (index, reason) in merged.conflicts {
+        let name = base_props[index].get(1).and_then(Value::as_str).unwrap_or("?").to_owned();
+        conflicts.push(MergeConflict::Prop { name, reason });
+    }
+    if merged.reordered {
+        conflicts.push(MergeConflict::Order { list: "props" });
+    }
+    project._props["#props"] = merged.items.into();
+
+    let cameras = projects.each_ref().map(|p| {
+        let list = |key: &str| p._cams.get(key).and_then(Value::as_array).cloned().unwrap_or_default();
+        list("#cameras").into_iter().zip(list("#quads")).collect::<Vec<_>>()
+    });
+    let by_index = |a: &[(Value, Value)], b: &[(Value, Value)]| (0..a.len().min(b.len())).map(|i| (i, i)).collect();
+    let merged = merge_list(&cameras[0], &cameras[1], &cameras[2], by_index, |b, o, t| {
+        pick(b, o, t).ok_or_else(|| "moved on both sides".into())
+    });
+    for (index, reason) in merged.conflicts {
+        conflicts.push(MergeConflict::Camera { index, reason });
+    }
+    let (positions, quads): (Vec<Value>, Vec<Value>) = merged.items.into_iter().unzip();
+    project._cams["#cameras"] = positions.into();
+    project._cams["#quads"] = quads.into();
+
+    for (line, skipped) in SETTINGS {
+        let lines = projects.each_ref().map(|p| settings_line(p, line));
+        let mut keys: Vec<&String> = vec![];
+        for key in lines.iter().flat_map(|l| l.as_object().into_iter().flatten().map(|(key, _)| key)) {
+            if !skipped.contains(&key.as_str()) && !is_editor_state(line, key) && !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+
+        for key in keys {
+            let values = lines.map(|l| l.get(key));
+            let merged = match pick(&values[0], &values[1], &values[2]) {
+                Some(value) => value,
+                None => {
+                    conflicts.push(MergeConflict::Setting { line, key: key.clone() });
+                    continue
+                }
+            };
+
+            if let Some(map) = settings_line_mut(&mut project, line).as_object_mut() {
+                match merged {
+                    Some(value) => map.insert(key.clone(), value.clone()),
+                    None => map.shift_remove(key),
+                };
+            }
+        }
+    }
+
+    Some(Merge {
+        level: RWLevel::from_project(ours.name().to_owned(), project)?,
+        conflicts,
+    })
+}
+
+/// Effects changed on both sides are merged cell by cell, and their options as a whole
+fn merge_effect(base: &Effect, ours: &Effect, theirs: &Effect) -> Result<Effect, String> {
+    if let Some(effect) = pick(base, ours, theirs) {
+        return Ok(effect)
+    }
+
+    let settings = [base, ours, theirs].map(effect_settings);
+    let mut merged = match pick(&settings[0], &settings[1], &settings[2]) {
+        Some(merged) if merged == settings[2] => theirs.clone(),
+        Some(_) => ours.clone(),
+        None => return Err("options changed on both sides".into()),
+    };
+
+    for (x, y, cell) in merged.matrix.iter_mut() {
+        let value = |effect: &Effect| effect.matrix().get(x, y).copied().unwrap_or_default();
+        *cell = pick(&value(base), &value(ours), &value(theirs))
+            .ok_or_else(|| format!("cell ({x}, {y}) changed on both sides"))?;
+    }
+
+    Ok(merged)
+}
+
+struct MergedList<T> {
+    items: Vec<T>,
+    /// Base index and reason of each conflicting item
+    conflicts: Vec<(usize, String)>,
+    /// Both sides changed the order of the list
+    reordered: bool,
+}
+
+/// Merge the items of a list. `matching` pairs the indices of the same items in
+/// two lists, and `merge_item` merges an item which both sides kept.
+/// The list keeps the order of whichever side reordered it, with the items
+/// added by the other side placed after the item they followed
+fn merge_list<T: Clone + PartialEq>(
+    base: &[T],
+    ours: &[T],
+    theirs: &[T],
+    matching: impl Fn(&[T], &[T]) -> Vec<(usize, usize)>,
+    merge_item: impl Fn(&T, &T, &T) -> Result<T, String>,
+) -> MergedList<T> {
+    let mut conflicts = vec![];
+    let (mo, mt) = (matching(base, ours), matching(base, theirs));
+    let find = |matched: &[(usize, usize)], bi: usize| matched.iter().find(|(b, _)| *b == bi).map(|(_, s)| *s);
+
+    let merged: Vec<Option<T>> = base.iter()
+        .enumerate()
+        .map(|(bi, b)| match (find(&mo, bi).map(|i| &ours[i]), find(&mt, bi).map(|i| &theirs[i])) {
+            (Some(o), Some(t)) => Some(merge_item(b, o, t).unwrap_or_else(|reason| {
+                conflicts.push((bi, reason));
+                o.clone()
+            })),
+            (Some(o), None) if o != b => {
+                conflicts.push((bi, "changed by us but removed by them".into()));
+                Some(o.clone())
+            }
+            (None, Some(t)) if t != b => {
+                conflicts.push((bi, "removed by us but changed by them".into()));
+                None
+            }
+            _ => None,
+        })
+        .collect();
+
+    // Sides which moved any of the items they kept
+    let moved = |matched: &[(usize, usize)]| {
+        let order: Vec<usize> = matched.iter().map(|(_, s)| *s).collect();
+        longest_increasing(&order).len() != order.len()
+    };
+    let (ours_moved, theirs_moved) = (moved(&mo), moved(&mt));
+    let ((main, mm), (other, om)) = match theirs_moved && !ours_moved {
+        true => ((theirs, &mt), (ours, &mo)),
+        false => ((ours, &mo), (theirs, &mt)),
+    };
+
+    // Items of the main side, and where in them each kept base item ended up
+    let mut items = vec![];
+    let mut anchors = vec![None; base.len()];
+    for (si, item) in main.iter().enumerate() {
+        match mm.iter().find(|(_, s)| *s == si) {
+            Some(&(bi, _)) => {
+                if let Some(merged) = &merged[bi] {
+                    items.push(merged.clone());
+                }
+                anchors[bi] = Some(items.len());
+            }
+            None => items.push(item.clone()),
+        }
+    }
+
+    // Items added by the other side go after the nearest item before them which the main side kept
+    let mut inserts = vec![];
+    for (si, item) in other.iter().enumerate() {
+        if om.iter().any(|(_, s)| *s == si) {
+            continue
+        }
+        let anchor = (0..si).rev()
+            .filter_map(|prev| om.iter().find(|(_, s)| *s == prev))
+            .find_map(|&(bi, _)| anchors[bi])
+            .unwrap_or(0);
+        inserts.push((anchor, item.clone()));
+    }
+    inserts.sort_by_key(|(anchor, _)| *anchor);
+
+    let mut list = Vec::with_capacity(items.len() + inserts.len());
+    let mut inserts = inserts.into_iter().peekable();
+    for pos in 0..=items.len() {
+        while let Some((_, item)) = inserts.next_if(|(anchor, _)| *anchor == pos) {
+            list.push(item);
+        }
+        if let Some(item) = items.get(pos) {
+            list.push(item.clone());
+        }
+    }
+
+    MergedList {
+        items: list,
+        conflicts,
+        reordered: ours_moved && theirs_moved,
+    }
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dimensions { ours, theirs } =>
+                write!(f, "size: {}x{} in ours but {}x{} in theirs", ours.0, ours.1, theirs.0, theirs.1),
+            Self::Tile { layer, x, y, ours, theirs } =>
+                write!(f, "L{} ({x}, {y}): {} in ours but {} in theirs", layer + 1, TileDesc(ours), TileDesc(theirs)),
+            Self::TileArt { layer, x, y } =>
+                write!(f, "L{} ({x}, {y}) tiles: changed on both sides", layer + 1),
+            Self::Effect { name, reason } =>
+                write!(f, "effect {name}: {reason}"),
+            Self::Prop { name, reason } =>
+                write!(f, "prop {name}: {reason}"),
+            Self::Camera { index, reason } =>
+                write!(f, "camera {index}: {reason}"),
+            Self::Order { list } =>
+                write!(f, "{list}: reordered on both sides"),
+            Self::Setting { line, key } =>
+                write!(f, "{line} setting {}: changed on both sides", key.trim_start_matches('#')),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rwlevel::fixture::{self, Edit}, RWLevel};
+
+    use super::{merge, MergeConflict};
+
+    fn level(rows: &[&str], effects: &[&str], props: &[&str]) -> RWLevel {
+        let effects: Vec<String> = effects.iter()
+            .map(|name| format!(r#"[#nm: "{name}", #mtrx: [[0, 0], [0, 0], [0, 0]], #Options: []]"#))
+            .collect();
+        let props: Vec<String> = props.iter()
+            .map(|name| format!(r#"[-5, "{name}", point(1, 1), [], [#seed: 1]]"#))
+            .collect();
+        let project = fixture::project(rows)
+            .edit("#effects: []", &format!("#effects: [{}]", effects.join(", ")))
+            .edit("#props: []", &format!("#props: [{}]", props.join(", ")));
+        RWLevel::from_project_str("TEST", &project).unwrap()
+    }
+
+    #[test]
+    fn test_clean_merge() {
+        let base = level(&["#.#", "#.#"], &["Slime", "Rust"], &["Pipe", "Rock"]);
+        let ours = level(&["#|#", "#.#"], &["Slime", "Rust", "Moss"], &["Pipe"]);
+        let theirs = level(&["#.#", "#-#"], &["Rust", "Slime"], &["Pipe", "Rock", "Bone"]);
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert!(merged.is_clean(), "{:?}", merged.conflicts());
+
+        let level = merged.level();
+        assert_eq!(crate::render::ascii(level, 0), "#|#\n#-#\n");
+        let names: Vec<&str> = level.effects().iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["Rust", "Moss", "Slime"]);
+        let props = level.to_raw_json()["props"]["#props"].clone();
+        let props: Vec<&str> = props.as_array().unwrap().iter().map(|p| p[1].as_str().unwrap()).collect();
+        assert_eq!(props, ["Pipe", "Bone"]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let base = level(&["#.#"], &[], &[]);
+        let ours = level(&["#|#"], &[], &[]);
+        let theirs = level(&["#-#"], &[], &[]);
+
+        let merged = merge(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.conflicts().len(), 1);
+        assert!(matches!(merged.conflicts()[0], MergeConflict::Tile { layer: 0, x: 1, y: 0, .. }));
+        assert_eq!(merged.conflicts()[0].to_string(), "L1 (1, 0): Air [VPole] in ours but Air [HPole] in theirs");
+        assert_eq!(crate::render::ascii(merged.level(), 0), "#|#\n");
+
+        // A short column in our tile matrix can't be merged into
+        let project = fixture::project(&["#.#"]).edit("#tlMatrix: []", "#tlMatrix: [[]]");
+        let short = RWLevel::from_project_str("TEST", &project).unwrap();
+        let merged = merge(&base, &short, &base).unwrap();
+        assert!(matches!(merged.conflicts(), [MergeConflict::TileArt { layer: 0, x: 0, y: 0 }, ..]));
+    }
+}
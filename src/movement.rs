@@ -0,0 +1,631 @@
+//! An approximate model of how creatures get around layer 1 of a room, used to
+//! find room exits and dens which can't be reached, and places a creature can
+//! get into but never leave.
+//!
+//! Every open tile is a position. What a creature can do from each is set by
+//! its [`MovementProfile`]. The slugcat stands on walls, floors and slopes,
+//! holds on to poles, swims below the water level and climbs shafts one tile
+//! wide by jumping between their walls. From any of those it can jump
+//! [`JUMP_HEIGHT`] tiles up and [`JUMP_LENGTH`] across, and from anywhere else it
+//! falls. Floors can be jumped up through and dropped down through. Real jumps
+//! depend on momentum and timing, so expect the odd tile either way.
+
+use std::collections::VecDeque;
+
+use crate::{
+    grid::Grid,
+    shortcuts::{RoomNode, ShortcutEnd, ShortcutNetwork},
+    Connectivity, Feature, FeatureSet, Geometry, RWLevel, Tile,
+};
+
+/// Tiles the slugcat can rise in a jump
+pub const JUMP_HEIGHT: usize = MovementProfile::SLUGCAT.jump_height;
+/// Tiles the slugcat can cover sideways in a jump
+pub const JUMP_LENGTH: usize = MovementProfile::SLUGCAT.jump_length;
+
+/// How a step between two positions is taken
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Move {
+    Walk,
+    /// Along a pole
+    Climb,
+    /// Along walls and ceilings
+    Crawl,
+    Jump,
+    Fall,
+    /// Down through a floor
+    Drop,
+    Swim,
+    Fly,
+    /// Through a shortcut to another entrance in the room
+    Shortcut,
+    /// Into one hive and out of another
+    Hive,
+}
+
+/// A way out of a position
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub to: (usize, usize),
+    pub kind: Move,
+}
+
+/// What a creature can do to get around a room
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MovementProfile {
+    /// Tiles it can rise in a jump
+    pub jump_height: usize,
+    /// Tiles it can cover sideways in a jump, 0 if it can't jump
+    pub jump_length: usize,
+    pub climbs_poles: bool,
+    /// Crawls along any wall or ceiling
+    pub climbs_walls: bool,
+    pub swims: bool,
+    pub flies: bool,
+    /// Which of [`Feature::Hive`], [`Feature::GopherHole`] and
+    /// [`Feature::ScavengerHole`] it uses. Holes are ways in and out of the
+    /// room, hives take it from one hive tile to any other
+    pub uses: FeatureSet,
+}
+
+impl MovementProfile {
+    pub const SLUGCAT: Self = Self {
+        jump_height: 3,
+        jump_length: 5,
+        climbs_poles: true,
+        climbs_walls: false,
+        swims: true,
+        flies: false,
+        uses: FeatureSet::new(),
+    };
+
+    pub const LIZARD: Self = Self {
+        jump_height: 1,
+        jump_length: 2,
+        climbs_poles: false,
+        climbs_walls: true,
+        swims: false,
+        flies: false,
+        uses: FeatureSet::new().with(Feature::GopherHole),
+    };
+
+    pub const SCAVENGER: Self = Self {
+        uses: FeatureSet::new().with(Feature::GopherHole).with(Feature::ScavengerHole),
+        ..Self::SLUGCAT
+    };
+
+    pub const BATFLY: Self = Self {
+        jump_height: 0,
+        jump_length: 0,
+        climbs_poles: false,
+        climbs_walls: false,
+        swims: false,
+        flies: true,
+        uses: FeatureSet::new().with(Feature::GopherHole).with(Feature::Hive),
+    };
+
+    /// A built-in profile by name, e.g. `lizard`
+    pub fn named(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "slugcat" => Self::SLUGCAT,
+            "lizard" => Self::LIZARD,
+            "scavenger" => Self::SCAVENGER,
+            "batfly" => Self::BATFLY,
+            _ => return None,
+        })
+    }
+}
+
+/// Every step possible from every open tile of layer 1
+#[derive(Clone, Debug)]
+pub struct MovementGraph {
+    steps: Grid<Vec<Step>>,
+    /// Positions from which the creature falls out of the bottom of the room
+    pits: Grid<bool>,
+}
+
+/// Which room nodes can be reached from which, see [`RWLevel::reachability`]
+#[derive(Clone, Debug)]
+pub struct Reachability {
+    /// Room exits then dens, as numbered by the game
+    pub nodes: Vec<RoomNode>,
+    /// Indices into `nodes` of the nodes reachable from each node's entrance
+    pub reachable: Vec<Vec<usize>>,
+    /// For room exits which lead somewhere the creature can't get back out of,
+    /// the exit's index and the lowest such position
+    pub stuck: Vec<(usize, (usize, usize))>,
+}
+
+/// Where a creature can get to in a room, see [`RWLevel::accessibility`]
+#[derive(Clone, Debug)]
+pub struct Accessibility {
+    /// Shortcut entrances of the room exits, and of the holes the creature uses
+    pub ways_out: Vec<(usize, usize)>,
+    /// Positions reachable after coming in through any of `ways_out`
+    pub reachable: Grid<bool>,
+    /// Positions from which one of `ways_out` can be reached
+    pub leaving: Grid<bool>,
+    /// Shortcut entrances of dens, and hive tiles for creatures which use
+    /// them, from which none of `ways_out` can be reached
+    pub stranded: Vec<(usize, usize)>,
+}
+
+impl MovementGraph {
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.steps.dimensions()
+    }
+
+    /// Steps out of (x, y). Empty for solid tiles and positions outside the room
+    pub fn steps(&self, x: usize, y: usize) -> &[Step] {
+        self.steps.get(x, y).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether the creature falls out of the room from (x, y)
+    pub fn is_pit(&self, x: usize, y: usize) -> bool {
+        self.pits.get(x, y).is_some_and(|pit| *pit)
+    }
+
+    /// Positions reachable from any of `starts`, including the starts themselves
+    pub fn reachable_from(&self, starts: impl IntoIterator<Item = (usize, usize)>) -> Grid<bool> {
+        let (w, h) = self.dimensions();
+        let mut seen = Grid::filled(w, h, false);
+        let mut queue = VecDeque::new();
+        for start in starts {
+            if self.steps.contains(start.0, start.1) && !seen[start] {
+                seen[start] = true;
+                queue.push_back(start);
+            }
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            for step in self.steps(x, y) {
+                if !seen[step.to] {
+                    seen[step.to] = true;
+                    queue.push_back(step.to);
+                }
+            }
+        }
+
+        seen
+    }
+
+    /// Positions from which any of `targets` can be reached
+    pub fn reaching(&self, targets: impl IntoIterator<Item = (usize, usize)>) -> Grid<bool> {
+        let (w, h) = self.dimensions();
+        let mut reverse = Grid::filled(w, h, vec![]);
+        for (x, y, steps) in self.steps.iter() {
+            for step in steps {
+                reverse[step.to].push(Step { to: (x, y), kind: step.kind });
+            }
+        }
+
+        MovementGraph { steps: reverse, pits: self.pits.clone() }.reachable_from(targets)
+    }
+
+    fn pits(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.pits.iter().filter(|(_, _, pit)| **pit).map(|(x, y, _)| (x, y))
+    }
+}
+
+impl RWLevel {
+    /// How a creature can move around layer 1
+    pub fn movement(&self, profile: &MovementProfile) -> MovementGraph {
+        let (w, h) = self.meta().dimensions();
+        let surface = usize::try_from(self.meta().water_level()).ok().map(|level| h.saturating_sub(level));
+        let terrain = Terrain { level: self, profile, surface };
+
+        let mut steps = Grid::filled(w, h, vec![]);
+        let mut pits = Grid::filled(w, h, false);
+        for y in 0..h {
+            for x in 0..w {
+                if terrain.open(x, y) {
+                    steps[(x, y)] = terrain.steps(x, y);
+                    pits[(x, y)] = y + 1 == h && !terrain.supported(x, y);
+                }
+            }
+        }
+
+        for shortcut in &self.shortcuts().shortcuts {
+            if let ShortcutEnd::Entrance(other) = shortcut.end {
+                if let Some(steps) = steps.get_mut(shortcut.entrance.0, shortcut.entrance.1) {
+                    steps.push(Step { to: other, kind: Move::Shortcut });
+                }
+            }
+        }
+
+        if profile.uses.contains(&Feature::Hive) {
+            let hives: Vec<(usize, usize)> = self.hives().collect();
+            for &from in &hives {
+                steps[from].extend(hives.iter().filter(|&&to| to != from).map(|&to| Step { to, kind: Move::Hive }));
+            }
+        }
+
+        MovementGraph { steps, pits }
+    }
+
+    /// How the slugcat can move around layer 1
+    pub fn slugcat_movement(&self) -> MovementGraph {
+        self.movement(&MovementProfile::SLUGCAT)
+    }
+
+    /// Which room exits and dens a creature can get between, starting from
+    /// the shortcut entrance of each
+    pub fn reachability(&self, profile: &MovementProfile) -> Reachability {
+        let graph = self.movement(profile);
+        let network = self.shortcuts();
+        let room = network.nodes();
+        let nodes: Vec<RoomNode> = room.exits.iter().chain(&room.dens).copied().collect();
+
+        let reachable: Vec<Grid<bool>> = nodes.iter().map(|node| graph.reachable_from([node.entrance])).collect();
+
+        let leaving = graph.reaching(ways_out(&network, profile).into_iter().chain(graph.pits()));
+        let stuck = reachable[..room.exits.len()].iter()
+            .enumerate()
+            .filter_map(|(idx, seen)| {
+                let pos = seen.iter().filter(|(x, y, seen)| **seen && !leaving[(*x, *y)]).last()?;
+                Some((idx, (pos.0, pos.1)))
+            })
+            .collect();
+
+        Reachability {
+            reachable: reachable.iter()
+                .map(|seen| (0..nodes.len()).filter(|&other| seen[nodes[other].entrance]).collect())
+                .collect(),
+            nodes,
+            stuck,
+        }
+    }
+
+    /// Which room exits and dens the slugcat can get between, starting from
+    /// the shortcut entrance of each
+    pub fn slugcat_reachability(&self) -> Reachability {
+        self.reachability(&MovementProfile::SLUGCAT)
+    }
+
+    /// Where a creature can get to from the ways into the room it uses, and
+    /// which of its dens can't get back out
+    pub fn accessibility(&self, profile: &MovementProfile) -> Accessibility {
+        let graph = self.movement(profile);
+        let network = self.shortcuts();
+        let ways_out = ways_out(&network, profile);
+
+        let reachable = graph.reachable_from(ways_out.iter().copied());
+        let leaving = graph.reaching(ways_out.iter().copied().chain(graph.pits()));
+
+        let dens = network.nodes().dens.into_iter().map(|den| den.entrance);
+        let hives = self.hives().filter(|_| profile.uses.contains(&Feature::Hive));
+        let stranded = dens.chain(hives).filter(|&pos| !leaving[pos]).collect();
+
+        Accessibility { ways_out, reachable, leaving, stranded }
+    }
+
+    fn hives(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.tiles(RWLevel::L1_FG)
+            .filter(|(_, _, tile)| tile.features.contains(&Feature::Hive) && !tile.geometry.is_solid())
+            .map(|(x, y, _)| (x, y))
+    }
+}
+
+/// Shortcut entrances which lead out of the room for a creature
+fn ways_out(network: &ShortcutNetwork, profile: &MovementProfile) -> Vec<(usize, usize)> {
+    network.shortcuts.iter()
+        .filter(|shortcut| match shortcut.end {
+            ShortcutEnd::RoomExit(_) => true,
+            ShortcutEnd::GopherHole(_) => profile.uses.contains(&Feature::GopherHole),
+            ShortcutEnd::ScavengerHole(_) => profile.uses.contains(&Feature::ScavengerHole),
+            _ => false,
+        })
+        .map(|shortcut| shortcut.entrance)
+        .collect()
+}
+
+/// Layer 1 as a creature sees it
+struct Terrain<'a> {
+    level: &'a RWLevel,
+    profile: &'a MovementProfile,
+    /// First row below the water's surface, if the room has water
+    surface: Option<usize>,
+}
+
+impl Terrain<'_> {
+    fn tile(&self, x: usize, y: usize) -> Option<Tile> {
+        self.level.tile(RWLevel::L1_FG, x, y)
+    }
+
+    /// Inside the room and not solid
+    fn open(&self, x: usize, y: usize) -> bool {
+        self.tile(x, y).is_some_and(|tile| !tile.geometry.is_solid())
+    }
+
+    fn geometry(&self, x: usize, y: usize) -> Option<Geometry> {
+        self.tile(x, y).map(|tile| tile.geometry)
+    }
+
+    fn neighbours(&self, x: usize, y: usize, connectivity: Connectivity) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.level.layer(RWLevel::L1_FG)
+            .into_iter()
+            .flat_map(move |layer| layer.geometry().neighbours(x, y, connectivity))
+    }
+
+    /// Standing on something, or in a slope
+    fn grounded(&self, x: usize, y: usize) -> bool {
+        is_slope(self.geometry(x, y)) || self.geometry(x, y + 1).is_some_and(|below| {
+            below.is_solid() || below == Geometry::Floor || is_slope(Some(below))
+        })
+    }
+
+    fn holding(&self, x: usize, y: usize) -> bool {
+        self.profile.climbs_poles && self.tile(x, y).is_some_and(|tile| {
+            tile.features.contains(&Feature::VPole) || tile.features.contains(&Feature::HPole)
+        })
+    }
+
+    fn swimming(&self, y: usize) -> bool {
+        self.profile.swims && self.surface.is_some_and(|surface| y >= surface)
+    }
+
+    /// Next to a wall it can crawl along
+    fn clinging(&self, x: usize, y: usize) -> bool {
+        self.profile.climbs_walls && self.neighbours(x, y, Connectivity::Four)
+            .any(|(nx, ny)| self.geometry(nx, ny).is_some_and(Geometry::is_solid))
+    }
+
+    /// Between walls close enough to climb by jumping from one to the other
+    fn in_shaft(&self, x: usize, y: usize) -> bool {
+        let solid = |x: Option<usize>| x.is_none_or(|x| self.geometry(x, y).is_none_or(Geometry::is_solid));
+        self.profile.jump_height > 0 && solid(x.checked_sub(1)) && solid(Some(x + 1))
+    }
+
+    /// Anywhere it doesn't fall from
+    fn supported(&self, x: usize, y: usize) -> bool {
+        self.profile.flies || self.grounded(x, y) || self.holding(x, y) || self.swimming(y) || self.clinging(x, y)
+    }
+
+    fn steps(&self, x: usize, y: usize) -> Vec<Step> {
+        let mut steps = vec![];
+        let mut push = |to: (usize, usize), kind| {
+            if self.open(to.0, to.1) && !steps.iter().any(|step: &Step| step.to == to) {
+                steps.push(Step { to, kind });
+            }
+        };
+        let sides = [x.checked_sub(1), Some(x + 1)];
+
+        let grounded = self.grounded(x, y);
+        let holding = self.holding(x, y);
+        let swimming = self.swimming(y);
+
+        if self.profile.flies {
+            for to in self.neighbours(x, y, Connectivity::Eight) {
+                push(to, Move::Fly);
+            }
+        }
+
+        if swimming {
+            for to in self.neighbours(x, y, Connectivity::Four) {
+                push(to, Move::Swim);
+            }
+        }
+
+        if self.clinging(x, y) {
+            for to in self.neighbours(x, y, Connectivity::Four).filter(|&(nx, ny)| self.clinging(nx, ny)) {
+                push(to, Move::Crawl);
+            }
+        }
+
+        if grounded {
+            for nx in sides.into_iter().flatten() {
+                push((nx, y), Move::Walk);
+            }
+        }
+
+        if holding {
+            let tile = self.tile(x, y).expect("Held tile is in the room");
+            if tile.features.contains(&Feature::VPole) {
+                for ny in [y.checked_sub(1), Some(y + 1)].into_iter().flatten() {
+                    push((x, ny), Move::Climb);
+                }
+            }
+            if tile.features.contains(&Feature::HPole) {
+                for nx in sides.into_iter().flatten() {
+                    push((nx, y), Move::Climb);
+                }
+            }
+        }
+
+        if self.profile.jump_length > 0 && (grounded || holding || swimming || self.in_shaft(x, y)) {
+            // Straight up as far as the ceiling allows, then across at each height
+            for rise in 0..=self.profile.jump_height {
+                let Some(top) = y.checked_sub(rise).filter(|&top| self.open(x, top)) else {
+                    break
+                };
+                if rise > 0 {
+                    push((x, top), Move::Jump);
+                }
+
+                for dir in [-1, 1] {
+                    for across in 1..=self.profile.jump_length as isize {
+                        let Some(nx) = x.checked_add_signed(dir * across).filter(|&nx| self.open(nx, top)) else {
+                            break
+                        };
+                        push((nx, top), Move::Jump);
+                    }
+                }
+            }
+        }
+
+        if self.geometry(x, y + 1) == Some(Geometry::Floor) {
+            push((x, y + 1), Move::Drop);
+        } else if !grounded {
+            push((x, y + 1), Move::Fall);
+            // Drifting sideways while falling
+            for nx in sides.into_iter().flatten() {
+                if self.open(nx, y) {
+                    push((nx, y + 1), Move::Fall);
+                }
+            }
+        }
+
+        steps
+    }
+}
+
+fn is_slope(geometry: Option<Geometry>) -> bool {
+    matches!(geometry, Some(Geometry::SlopeNE | Geometry::SlopeNW | Geometry::SlopeSE | Geometry::SlopeSW))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{lint::Severity, rwlevel::fixture::{self, Edit}, FeatureSet, RWLevel};
+
+    use super::{Move, MovementProfile};
+
+    #[test]
+    fn test_movement() {
+        let level = fixture::level(&[
+            "#########",
+            "#.......#",
+            "#|..#...#",
+            "#|..#...#",
+            "#|..#...#",
+            "#|..#...#",
+            "#########",
+        ]);
+        let graph = level.slugcat_movement();
+
+        assert!(graph.steps(0, 0).is_empty());
+        let steps = |x, y| graph.steps(x, y).iter().map(|step| (step.to, step.kind)).collect::<Vec<_>>();
+        assert!(steps(1, 5).contains(&((1, 4), Move::Climb)));
+        assert!(steps(2, 5).contains(&((3, 5), Move::Walk)));
+        assert!(steps(6, 1).contains(&((6, 2), Move::Fall)));
+
+        // The wall is too tall to jump over from the floor, but not from the top of the pole
+        assert!(graph.reachable_from([(2, 5)])[(6, 5)]);
+        assert!(!graph.reachable_from([(6, 5)])[(2, 5)]);
+
+        // Lizards go straight up the wall instead
+        assert!(level.movement(&MovementProfile::LIZARD).reachable_from([(6, 5)])[(2, 5)]);
+    }
+
+    #[test]
+    fn test_water() {
+        let rows = [
+            "#########",
+            "#.......#",
+            "#.......#",
+            "#.......#",
+            "###...###",
+            "###...###",
+            "###...###",
+            "###...###",
+            "###...###",
+            "#########",
+        ];
+
+        // Too deep to jump out of when dry
+        let dry = fixture::level(&rows);
+        assert!(!dry.slugcat_movement().reachable_from([(4, 8)])[(1, 1)]);
+
+        // Filled up to row 4 it can swim to the top and jump out from there
+        let project = fixture::project(&rows).edit("#waterLevel: -1", "#waterLevel: 6");
+        let wet = RWLevel::from_project_str("TEST", &project).unwrap();
+        let graph = wet.slugcat_movement();
+        assert!(graph.steps(4, 6).iter().any(|step| step.to == (4, 5) && step.kind == Move::Swim));
+        assert!(!graph.steps(4, 3).iter().any(|step| step.kind == Move::Swim));
+        assert!(graph.reachable_from([(4, 8)])[(1, 1)]);
+
+        // A creature which can't swim sinks
+        let sinks = MovementProfile { swims: false, ..MovementProfile::SLUGCAT };
+        assert!(!wet.movement(&sinks).reachable_from([(4, 8)])[(1, 1)]);
+        assert!(!wet.movement(&sinks).steps(4, 6).iter().any(|step| step.kind == Move::Swim));
+    }
+
+    #[test]
+    fn test_flying() {
+        let level = fixture::level(&[
+            "#######",
+            "#H#H..#",
+            "#.#.#.#",
+            "#.#...#",
+            "#######",
+        ]);
+
+        // Flyers go anywhere open, diagonals included, and never fall
+        let graph = level.movement(&MovementProfile::BATFLY);
+        let steps = |x, y| graph.steps(x, y).iter().map(|step| (step.to, step.kind)).collect::<Vec<_>>();
+        assert!(steps(5, 2).contains(&((4, 3), Move::Fly)));
+        assert!(!steps(5, 2).iter().any(|(_, kind)| *kind == Move::Fall));
+
+        // Hives lead to each other, across walls
+        assert_eq!(steps(1, 1).iter().filter(|(_, kind)| *kind == Move::Hive).collect::<Vec<_>>(), [&((3, 1), Move::Hive)]);
+        assert!(graph.reachable_from([(1, 3)])[(5, 3)]);
+
+        let no_hives = MovementProfile { uses: FeatureSet::new(), ..MovementProfile::BATFLY };
+        assert!(!level.movement(&no_hives).reachable_from([(1, 3)])[(5, 3)]);
+        assert!(level.movement(&no_hives).steps(1, 1).iter().all(|step| step.kind != Move::Hive));
+
+        // An open floor is a pit to walkers, but not to flyers
+        let level = fixture::level(&["#.#", "#.#"]);
+        assert!(level.slugcat_movement().is_pit(1, 1));
+        assert!(!level.movement(&MovementProfile::BATFLY).is_pit(1, 1));
+    }
+
+    #[test]
+    fn test_reachability() {
+        let rows = [
+            "####E####",
+            "####>####",
+            "#.......#",
+            "#.......#",
+            "#####...#",
+            "#####...#",
+            "#####...#",
+            "#####...#",
+            "###E>...#",
+            "#########",
+        ];
+
+        // Dropping into the pit to the lower exit is fine, but there's no way back up
+        let reach = fixture::level(&rows).slugcat_reachability();
+        assert_eq!(reach.nodes.iter().map(|node| node.entrance).collect::<Vec<_>>(), [(4, 8), (4, 1)]);
+        assert_eq!(reach.reachable, [vec![0], vec![0, 1]]);
+        assert!(reach.stuck.is_empty());
+
+        let mut rows = rows;
+        rows[8] = "#####...#";
+        let level = fixture::level(&rows);
+        assert_eq!(level.slugcat_reachability().stuck, [(0, (7, 8))]);
+
+        // Reported as info, so it doesn't fail lint runs
+        let lints: Vec<_> = level.lint().into_iter().map(|d| (d.rule, d.severity)).collect();
+        assert_eq!(lints, [("RW011", Severity::Info)]);
+    }
+
+    #[test]
+    fn test_accessibility() {
+        let level = fixture::level(&[
+            "####E####",
+            "####>####",
+            "#.......#",
+            "#H......#",
+            "#####...#",
+            "#H###...#",
+            "#.###...#",
+            "#H###...#",
+            "#>###...#",
+            "#D#######",
+        ]);
+
+        // The den is sealed off, but has hives on both sides for batflies
+        for profile in [MovementProfile::SLUGCAT, MovementProfile::LIZARD, MovementProfile::SCAVENGER] {
+            let access = level.accessibility(&profile);
+            assert_eq!(access.ways_out, [(4, 1)]);
+            assert_eq!(access.stranded, [(1, 8)]);
+            assert!(access.reachable[(1, 3)]);
+            assert!(!access.reachable[(1, 6)]);
+        }
+
+        let batfly = level.accessibility(&MovementProfile::BATFLY);
+        assert!(batfly.stranded.is_empty());
+        assert!(batfly.reachable[(1, 8)] && batfly.leaving[(6, 8)]);
+    }
+}
@@ -0,0 +1,521 @@
+//! Patches replaying the changes of a [diff](crate::diff) onto another copy of a level.
+//!
+//! A patch is stored as JSON:
+//!
+//! ```text
+//! { "format": "rainworld_level_patch", "version": 1, "changes": [ ... ] }
+//! ```
+//!
+//! where `changes` is the output of [LevelDiff::to_json]. Every change records
+//! the value it replaces, and applying a patch fails without modifying the level
+//! if any of those values differ from what the level holds.
+
+use std::fmt;
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    diff::{diff, settings_line_mut, Change, LevelDiff, SETTINGS},
+    Effect, Feature, Geometry, Point, RWLevel, Tile,
+};
+
+pub const FORMAT: &str = "rainworld_level_patch";
+pub const PATCH_VERSION: u64 = 1;
+
+#[derive(Clone, Debug, Default)]
+pub struct Patch {
+    changes: Vec<Change>,
+}
+
+/// A change of a patch which doesn't match the level it's applied to
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub change: Change,
+    pub reason: String,
+}
+
+impl Patch {
+    /// The patch turning `old` into `new`
+    pub fn between(old: &RWLevel, new: &RWLevel) -> Self {
+        diff(old, new).into()
+    }
+
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "format": FORMAT,
+            "version": PATCH_VERSION,
+            "changes": self.changes.iter().map(Change::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        if value.get("format").and_then(Value::as_str) != Some(FORMAT) {
+            return Err(format!("not a {FORMAT} document"))
+        }
+        match value.get("version").and_then(Value::as_u64) {
+            Some(PATCH_VERSION) => {}
+            Some(version) => return Err(format!("unsupported patch version {version}")),
+            None => return Err("missing patch version".into()),
+        }
+
+        let changes = value.get("changes")
+            .and_then(Value::as_array)
+            .ok_or("missing changes")?
+            .iter()
+            .enumerate()
+            .map(|(idx, change)| Change::from_json(change).ok_or_else(|| format!("bad change {idx}")))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { changes })
+    }
+}
+
+impl From<LevelDiff> for Patch {
+    fn from(diff: LevelDiff) -> Self {
+        Self { changes: diff.changes }
+    }
+}
+
+impl RWLevel {
+    /// Replay the changes of a patch. If any change conflicts with the level,
+    /// the level is left as it was and every conflict is returned
+    pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), Vec<Conflict>> {
+        let mut conflicts = vec![];
+        let mut conflict = |change: &Change, reason: String| conflicts.push(Conflict { change: change.clone(), reason });
+
+        let mut level = self.clone();
+        for change in &patch.changes {
+            if let Change::Dimensions { old, new } = change {
+                match level.meta().dimensions() == *old {
+                    true => level.resize(new.0, new.1),
+                    false => conflict(change, format!("level is {}x{}", level.meta().dimensions().0, level.meta().dimensions().1)),
+                }
+            }
+        }
+
+        let mut project = level.to_project();
+        let mut effects = Reorder::new(&project._effects, "#effects");
+        let mut props = Reorder::new(&project._props, "#props");
+        let mut removed_cameras = vec![];
+        let mut added_cameras = vec![];
+
+        for change in &patch.changes {
+            let result = match change {
+                Change::Dimensions { .. } => Ok(()),
+                // Compared as tiles, as the project may list the features in another order
+                Change::Tile { layer, x, y, old, new } => match level.tile(*layer, *x, *y) {
+                    Some(tile) if tile == *old => {
+                        project._geom[*x][*y][*layer] = new.to_json();
+                        Ok(())
+                    }
+                    Some(tile) => Err(format!("found {}", tile.to_json())),
+                    None => Err("out of bounds".into()),
+                },
+                Change::TileArt { layer, x, y, old, new } => {
+                    let cell = project._tiles.get_mut("#tlMatrix")
+                        .and_then(|m| m.get_mut(*x)?.get_mut(*y)?.get_mut(*layer));
+                    replace(cell, old, new.clone())
+                }
+                Change::EffectRemoved { index, name } => effects.remove(*index, |e| e.get("#nm").and_then(Value::as_str) == Some(name)),
+                Change::EffectAdded { index, effect } => effects.add(change, *index, effect.to_json()),
+                Change::EffectMoved { name, from, to } => effects.moved(change, *from, *to, |e| e.get("#nm").and_then(Value::as_str) == Some(name)),
+                Change::PropRemoved { index, prop } => props.remove(*index, |p| p == prop),
+                Change::PropAdded { index, prop } => props.add(change, *index, prop.clone()),
+                Change::PropChanged { old_index, index, old, new } => props.changed(change, *old_index, *index, old, new.clone()),
+                Change::CameraMoved { index, old, new } => {
+                    let cell = project._cams.get_mut("#cameras").and_then(|c| c.get_mut(*index));
+                    replace(cell, &point(old), point(new))
+                }
+                Change::CameraQuad { index, old, new } => {
+                    let cell = project._cams.get_mut("#quads").and_then(|q| q.get_mut(*index));
+                    replace(cell, old, new.clone())
+                }
+                Change::CameraRemoved { index, pos } => {
+                    removed_cameras.push((*index, point(pos), change));
+                    Ok(())
+                }
+                Change::CameraAdded { index, pos, quad } => {
+                    added_cameras.push((*index, point(pos), quad.clone(), change));
+                    Ok(())
+                }
+                Change::Setting { line, key, .. } if is_skipped(line, key) => Err(format!("{key} can't be set directly")),
+                Change::Setting { line, key, old, new } => {
+                    let line = settings_line_mut(&mut project, line);
+                    match line.get(key) == old.as_ref() {
+                        true => {
+                            if let Some(map) = line.as_object_mut() {
+                                match new {
+                                    Some(new) => map.insert(key.clone(), new.clone()),
+                                    None => map.shift_remove(key),
+                                };
+                            }
+                            Ok(())
+                        }
+                        false => Err(format!("setting is {}", line.get(key).map_or("missing".into(), Value::to_string))),
+                    }
+                }
+                // Applied once the effects are in their new order
+                Change::EffectMatrix { .. } | Change::EffectSettings { .. } => Ok(()),
+            };
+
+            if let Err(reason) = result {
+                conflict(change, reason);
+            }
+        }
+
+        // Cameras are only ever removed from or added to the end of the list
+        removed_cameras.sort_by_key(|(index, ..)| std::cmp::Reverse(*index));
+        for (index, pos, change) in removed_cameras {
+            let cams = project._cams.get_mut("#cameras").and_then(Value::as_array_mut);
+            match cams {
+                Some(cams) if index + 1 == cams.len() && cams[index] == pos => {
+                    cams.pop();
+                    if let Some(quads) = project._cams.get_mut("#quads").and_then(Value::as_array_mut) {
+                        quads.truncate(index);
+                    }
+                }
+                _ => conflict(change, format!("camera {index} isn't the last camera, or has moved")),
+            }
+        }
+        added_cameras.sort_by_key(|(index, ..)| *index);
+        for (index, pos, quad, change) in added_cameras {
+            let count = project._cams.get("#cameras").and_then(Value::as_array).map_or(0, Vec::len);
+            if index != count {
+                conflict(change, format!("level has {count} cameras"));
+                continue
+            }
+            for (key, value) in [("#cameras", pos), ("#quads", quad)] {
+                if let Some(list) = project._cams.get_mut(key).and_then(Value::as_array_mut) {
+                    list.push(value);
+                }
+            }
+        }
+
+        for (reorder, line, key) in [(effects, &mut project._effects, "#effects"), (props, &mut project._props, "#props")] {
+            match reorder.finish() {
+                Ok(list) => line[key] = list.into(),
+                Err((change, reason)) => conflict(change, reason),
+            }
+        }
+
+        let effects = project._effects.get_mut("#effects").and_then(Value::as_array_mut);
+        let mut effects = effects.map(std::mem::take).unwrap_or_default();
+        for change in &patch.changes {
+            let result = match change {
+                Change::EffectMatrix { index, name, cells } => effect_named(&mut effects, *index, name).and_then(|effect| {
+                    for (x, y, old, new) in cells {
+                        let cell = effect.get_mut("#mtrx").and_then(|m| m.get_mut(*x)?.get_mut(*y));
+                        match cell {
+                            Some(cell) if cell.as_f64() == Some(*old) => *cell = json!(new),
+                            _ => return Err(format!("cell ({x}, {y}) isn't {old}")),
+                        }
+                    }
+                    Ok(())
+                }),
+                Change::EffectSettings { index, name, old, new } => effect_named(&mut effects, *index, name).and_then(|effect| {
+                    let (Some(map), Some(new)) = (effect.as_object_mut(), new.as_object()) else {
+                        return Err("effect isn't a property list".into())
+                    };
+                    let current: Map<String, Value> = map.iter()
+                        .filter(|(key, _)| *key != "#mtrx")
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect();
+                    if Value::Object(current) != *old {
+                        return Err("options have been changed".into())
+                    }
+
+                    // Replace keys in place so the entry keeps the editor's order
+                    map.retain(|key, _| key == "#mtrx" || new.contains_key(key));
+                    for (key, value) in new {
+                        map.insert(key.clone(), value.clone());
+                    }
+                    Ok(())
+                }),
+                _ => Ok(()),
+            };
+
+            if let Err(reason) = result {
+                conflict(change, reason);
+            }
+        }
+        project._effects["#effects"] = effects.into();
+
+        if !conflicts.is_empty() {
+            return Err(conflicts)
+        }
+
+        match RWLevel::from_project(self.name().to_owned(), project) {
+            Some(level) => {
+                *self = level;
+                Ok(())
+            }
+            // Settings and effect options are copied over as they are, so one of them is to blame
+            None => Err(patch.changes.iter()
+                .filter(|change| matches!(change, Change::Setting { .. } | Change::EffectSettings { .. }))
+                .map(|change| Conflict { change: change.clone(), reason: "leaves the level unreadable".into() })
+                .collect()),
+        }
+    }
+}
+
+/// Whether a setting is only changed through another change, like `#size` through [Change::Dimensions]
+fn is_skipped(line: &str, key: &str) -> bool {
+    SETTINGS.iter().any(|(name, skipped)| *name == line && skipped.contains(&key))
+}
+
+fn effect_named<'a>(effects: &'a mut [Value], index: usize, name: &str) -> Result<&'a mut Value, String> {
+    match effects.get_mut(index) {
+        Some(effect) if effect.get("#nm").and_then(Value::as_str) == Some(name) => Ok(effect),
+        Some(_) => Err(format!("effect {index} isn't {name}")),
+        None => Err(format!("there is no effect {index}")),
+    }
+}
+
+/// Set a value if it's currently `old`
+fn replace(cell: Option<&mut Value>, old: &Value, new: Value) -> Result<(), String> {
+    match cell {
+        Some(cell) if cell == old => {
+            *cell = new;
+            Ok(())
+        }
+        Some(cell) => Err(format!("found {cell}")),
+        None => Err("out of bounds".into()),
+    }
+}
+
+fn point(p: &Point) -> Value {
+    Value::String(format!("point({}, {})", p.fst, p.snd))
+}
+
+/// Rebuilds a list from the removals, additions and moves of a diff. Indices of
+/// removals are in the old list, and of everything else in the new list.
+/// Entries which weren't touched keep their order and fill the remaining slots
+struct Reorder<'a> {
+    old: Vec<Option<Value>>,
+    placed: Vec<(usize, Value, &'a Change)>,
+}
+
+impl<'a> Reorder<'a> {
+    fn new(line: &Value, key: &str) -> Self {
+        let old = line.get(key)
+            .and_then(Value::as_array)
+            .map(|list| list.iter().cloned().map(Some).collect())
+            .unwrap_or_default();
+
+        Self { old, placed: vec![] }
+    }
+
+    /// Take the entry at `index` of the old list, if it passes the check
+    fn take(&mut self, index: usize, check: impl Fn(&Value) -> bool) -> Result<Value, String> {
+        match self.old.get_mut(index) {
+            Some(slot) if slot.as_ref().is_some_and(check) => Ok(slot.take().unwrap_or_default()),
+            Some(_) => Err(format!("entry {index} doesn't match")),
+            None => Err(format!("there is no entry {index}")),
+        }
+    }
+
+    fn remove(&mut self, index: usize, check: impl Fn(&Value) -> bool) -> Result<(), String> {
+        self.take(index, check).map(drop)
+    }
+
+    fn add(&mut self, change: &'a Change, index: usize, value: Value) -> Result<(), String> {
+        self.placed.push((index, value, change));
+        Ok(())
+    }
+
+    fn moved(&mut self, change: &'a Change, from: usize, to: usize, check: impl Fn(&Value) -> bool) -> Result<(), String> {
+        let value = self.take(from, check)?;
+        self.add(change, to, value)
+    }
+
+    fn changed(&mut self, change: &'a Change, from: usize, to: usize, old: &Value, new: Value) -> Result<(), String> {
+        self.take(from, |v| v == old)?;
+        self.add(change, to, new)
+    }
+
+    /// The new list, or the first entry which doesn't fit into it
+    fn finish(mut self) -> Result<Vec<Value>, (&'a Change, String)> {
+        let mut kept = self.old.into_iter().flatten();
+        let len = kept.clone().count() + self.placed.len();
+        let mut new: Vec<Option<Value>> = vec![None; len];
+
+        self.placed.sort_by_key(|(index, ..)| *index);
+        for (index, value, change) in self.placed {
+            match new.get_mut(index) {
+                Some(slot @ None) => *slot = Some(value),
+                _ => return Err((change, format!("entry {index} doesn't fit in a list of {len}"))),
+            }
+        }
+
+        Ok(new.into_iter().map(|slot| slot.or_else(|| kept.next()).unwrap_or_default()).collect())
+    }
+}
+
+impl Change {
+    /// Inverse of [Change::to_json]
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let usize_of = |key: &str| value.get(key)?.as_u64().map(|v| v as usize);
+        let layer = || usize_of("layer")?.checked_sub(1).filter(|&layer| layer < 3);
+        let string = |key: &str| Some(value.get(key)?.as_str()?.to_owned());
+        let val = |key: &str| value.get(key).cloned();
+        let pair = |v: &Value| Some((v.get(0)?.as_i64()?, v.get(1)?.as_i64()?));
+        let pos = |key: &str| pair(value.get(key)?).map(|(fst, snd)| Point { fst: fst as isize, snd: snd as isize });
+        let dims = |key: &str| pair(value.get(key)?).map(|(w, h)| (w as usize, h as usize));
+        let tile = |key: &str| {
+            let cell = value.get(key)?;
+            let geometry = Geometry::from_data(cell.get(0)?.as_u64()?.try_into().ok()?)?;
+            let features = cell.get(1)?
+                .as_array()?
+                .iter()
+                .map(|f| Feature::from_data(f.as_u64()?.try_into().ok()?))
+                .collect::<Option<_>>()?;
+            Some(Tile { geometry, features })
+        };
+
+        Some(match value.get("change")?.as_str()? {
+            "dimensions" => Self::Dimensions { old: dims("old")?, new: dims("new")? },
+            "tile" => Self::Tile { layer: layer()?, x: usize_of("x")?, y: usize_of("y")?, old: tile("old")?, new: tile("new")? },
+            "tileArt" => Self::TileArt { layer: layer()?, x: usize_of("x")?, y: usize_of("y")?, old: val("old")?, new: val("new")? },
+            "effectAdded" => Self::EffectAdded { index: usize_of("index")?, effect: Effect::from_entry(value.get("effect")?)? },
+            "effectRemoved" => Self::EffectRemoved { index: usize_of("index")?, name: string("name")? },
+            "effectMoved" => Self::EffectMoved { name: string("name")?, from: usize_of("from")?, to: usize_of("to")? },
+            "effectMatrix" => Self::EffectMatrix {
+                index: usize_of("index")?,
+                name: string("name")?,
+                cells: value.get("cells")?
+                    .as_array()?
+                    .iter()
+                    .map(|cell| Some((
+                        cell.get("x")?.as_u64()? as usize,
+                        cell.get("y")?.as_u64()? as usize,
+                        cell.get("old")?.as_f64()?,
+                        cell.get("new")?.as_f64()?,
+                    )))
+                    .collect::<Option<_>>()?,
+            },
+            "effectSettings" => Self::EffectSettings { index: usize_of("index")?, name: string("name")?, old: val("old")?, new: val("new")? },
+            "cameraAdded" => Self::CameraAdded { index: usize_of("index")?, pos: pos("pos")?, quad: val("quad")? },
+            "cameraRemoved" => Self::CameraRemoved { index: usize_of("index")?, pos: pos("pos")? },
+            "cameraMoved" => Self::CameraMoved { index: usize_of("index")?, old: pos("old")?, new: pos("new")? },
+            "cameraQuad" => Self::CameraQuad { index: usize_of("index")?, old: val("old")?, new: val("new")? },
+            "propAdded" => Self::PropAdded { index: usize_of("index")?, prop: val("prop")? },
+            "propRemoved" => Self::PropRemoved { index: usize_of("index")?, prop: val("prop")? },
+            "propChanged" => Self::PropChanged { old_index: usize_of("oldIndex")?, index: usize_of("index")?, old: val("old")?, new: val("new")? },
+            "setting" => {
+                let line = string("line")?;
+                let key = string("key")?;
+                Self::Setting {
+                    line: SETTINGS.iter().map(|(name, _)| *name).find(|name| *name == line && !is_skipped(name, &key))?,
+                    key,
+                    old: val("old").filter(|v| !v.is_null()),
+                    new: val("new").filter(|v| !v.is_null()),
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.change, self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{diff::{diff, Change}, rwlevel::fixture::{self, Edit}, RWLevel};
+
+    use super::Patch;
+
+    fn level(rows: &[&str], effects: &str, props: &str) -> RWLevel {
+        let project = fixture::project(rows)
+            .edit("#effects: []", &format!("#effects: [{effects}]"))
+            .edit("#props: []", &format!("#props: [{props}]"));
+        RWLevel::from_project_str("TEST", &project).unwrap()
+    }
+
+    const SLIME: &str = r#"[#nm: "Slime", #mtrx: [[0, 0], [0, 0], [0, 0]], #Options: [["Seed", [], 4]]]"#;
+    const RUST: &str = r#"[#nm: "Rust", #mtrx: [[0, 0], [0, 0], [0, 0]], #Options: []]"#;
+    const PIPE: &str = r#"[-5, "Pipe", point(1, 1), [], [#seed: 1]]"#;
+    const ROCK: &str = r#"[-2, "Rock", point(1, 2), [], [#seed: 1]]"#;
+
+    #[test]
+    fn test_apply() {
+        let old = level(&["#.#", "#>#"], &format!("{SLIME}, {RUST}"), &format!("{PIPE}, {ROCK}"));
+        let new = level(
+            &["#|#", "#>#", "#.#"],
+            &format!("{}, {}", RUST.edit("[0, 0], [0, 0]]", "[0, 0], [0, 30]]"), SLIME.edit("4]", "7]")),
+            &format!("{}, {PIPE}", ROCK.edit("-2", "-3")),
+        );
+
+        let patch = Patch::between(&old, &new);
+        let patch = Patch::from_json(&patch.to_json()).unwrap();
+
+        let mut patched = level(&["#.#", "#>#"], &format!("{SLIME}, {RUST}"), &format!("{PIPE}, {ROCK}"));
+        patched.apply_patch(&patch).unwrap();
+        assert!(diff(&patched, &new).is_empty(), "{}", diff(&patched, &new));
+        assert_eq!(patched.meta().dimensions(), (3, 3));
+        assert_eq!(patched.effects()[0].matrix()[(2, 1)], 30.0);
+    }
+
+    #[test]
+    fn test_conflict() {
+        let old = level(&["#.#"], "", PIPE);
+        let new = level(&["#|#"], "", ROCK);
+        let patch = Patch::between(&old, &new);
+
+        let mut other = level(&["#-#"], "", PIPE);
+        let before = other.to_project_string();
+        let conflicts = other.apply_patch(&patch).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].to_string(), "L1 (1, 0): Air -> Air [VPole]: found [0,[1]]");
+        assert_eq!(other.to_project_string(), before);
+    }
+
+    #[test]
+    fn test_size_setting() {
+        let change = json!({"change": "setting", "line": "render", "key": "#size", "old": "point(3, 1)", "new": "point(5, 5)"});
+        let patch = json!({"format": super::FORMAT, "version": super::PATCH_VERSION, "changes": [change]});
+        assert_eq!(Patch::from_json(&patch).unwrap_err(), "bad change 0");
+
+        let mut level = level(&["#.#"], "", "");
+        let before = level.to_project_string();
+        let patch = Patch {
+            changes: vec![Change::Setting { line: "render", key: "#size".into(), old: Some(json!("point(3, 1)")), new: Some(json!("point(5, 5)")) }],
+        };
+        let conflicts = level.apply_patch(&patch).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].reason, "#size can't be set directly");
+        assert_eq!(level.to_project_string(), before);
+    }
+
+    #[test]
+    fn test_unreadable_effect() {
+        let old = level(&["#.#"], SLIME, "");
+        let new = level(&["#.#"], &SLIME.edit("4]", "7]"), "");
+        let mut patch = Patch::between(&old, &new);
+        for change in &mut patch.changes {
+            if let Change::EffectSettings { new, .. } = change {
+                new.as_object_mut().unwrap().shift_remove("#nm");
+            }
+        }
+
+        let mut level = level(&["#.#"], SLIME, "");
+        let before = level.to_project_string();
+        let conflicts = level.apply_patch(&patch).unwrap_err();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].reason, "leaves the level unreadable");
+        assert_eq!(level.to_project_string(), before);
+    }
+}
@@ -0,0 +1,174 @@
+//! Quick previews of level geometry, either as text or as a PPM image.
+
+use crate::{Feature, Geometry, RWLevel, Tile};
+
+/// Text rendering of one layer, a row of chars per row of tiles.
+/// Uses the same characters as the test fixtures, so a rendered layer 1
+/// can be pasted into a test
+pub fn ascii(level: &RWLevel, layer: usize) -> String {
+    let (w, h) = level.meta().dimensions();
+    let mut out = String::with_capacity((w + 1) * h);
+
+    for y in 0..h {
+        for x in 0..w {
+            out.push(level.tile(layer, x, y).map_or(' ', |tile| tile_char(&tile)));
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+fn tile_char(tile: &Tile) -> char {
+    let has = |feature| tile.features().contains(&feature);
+
+    if tile.is_shortcut_entrance() {
+        return '>'
+    }
+
+    let feature = [
+        (Feature::RoomEntrance, 'E'),
+        (Feature::CreatureDen, 'D'),
+        (Feature::ScavengerHole, 'S'),
+        (Feature::GopherHole, 'M'),
+        (Feature::ShortcutPathDot, 'o'),
+        (Feature::WormGrass, 'w'),
+        (Feature::Hive, 'H'),
+    ]
+        .into_iter()
+        .find(|(feature, _)| has(*feature));
+    if let Some((_, c)) = feature {
+        return c
+    }
+
+    match (has(Feature::HPole), has(Feature::VPole), tile.geometry) {
+        (_, true, Geometry::Wall) => 'I',
+        (true, true, _) => '+',
+        (true, false, _) => '-',
+        (false, true, _) => '|',
+        (_, _, geometry) => match geometry {
+            Geometry::Air => '.',
+            Geometry::Wall => '#',
+            // Drawn as the edge between the solid and empty halves
+            Geometry::SlopeSE | Geometry::SlopeNW => '/',
+            Geometry::SlopeSW | Geometry::SlopeNE => '\\',
+            Geometry::Floor => '=',
+            Geometry::ShortcutEntrance => '>',
+            Geometry::Glass => 'G',
+        },
+    }
+}
+
+/// Binary PPM (P6) image of all three layers, `scale` pixels per tile.
+/// Deeper layers are drawn paler, and layer 1 features are drawn as a dot
+/// in the middle of their tile
+pub fn ppm(level: &RWLevel, scale: usize) -> Vec<u8> {
+    let scale = scale.max(1);
+    let (w, h) = level.meta().dimensions();
+    let (pw, ph) = (w * scale, h * scale);
+
+    let mut out = format!("P6\n{pw} {ph}\n255\n").into_bytes();
+    out.reserve(pw * ph * 3);
+
+    for py in 0..ph {
+        for px in 0..pw {
+            let (x, y) = (px / scale, py / scale);
+            let (sx, sy) = (px % scale, py % scale);
+            out.extend_from_slice(&pixel(level, x, y, (sx, sy), scale));
+        }
+    }
+
+    out
+}
+
+const SKY: [u8; 3] = [235, 235, 240];
+
+fn pixel(level: &RWLevel, x: usize, y: usize, (sx, sy): (usize, usize), scale: usize) -> [u8; 3] {
+    let fg = level.tile(RWLevel::L1_FG, x, y);
+
+    // Features take up the middle third of the tile
+    let middle = |s: usize| scale < 3 || (s >= scale / 3 && s < scale - scale / 3);
+    if let Some(color) = fg.as_ref().and_then(feature_color) {
+        if middle(sx) && middle(sy) {
+            return color
+        }
+    }
+
+    if let Some(tile) = fg {
+        if let Some(color) = geometry_color(tile.geometry, (sx, sy), scale) {
+            return color
+        }
+    }
+
+    let behind = [(RWLevel::L2_MG, 150), (RWLevel::L3_BG, 200)];
+    for (layer, shade) in behind {
+        let solid = level.tile(layer, x, y)
+            .is_some_and(|tile| geometry_color(tile.geometry, (sx, sy), scale).is_some());
+        if solid {
+            return [shade, shade, shade + 10]
+        }
+    }
+
+    SKY
+}
+
+/// Color of the geometry at a sub-tile pixel, or None if it's empty there
+fn geometry_color(geometry: Geometry, (sx, sy): (usize, usize), scale: usize) -> Option<[u8; 3]> {
+    // Mirror the sub-tile Y so slopes can be tested with a single comparison
+    let flip = scale - 1 - sy;
+    let filled = match geometry {
+        Geometry::Air => false,
+        Geometry::Wall | Geometry::ShortcutEntrance => true,
+        Geometry::Floor => sy < scale.div_ceil(2),
+        Geometry::Glass => return Some([170, 210, 230]),
+        Geometry::SlopeSW => sx <= sy,
+        Geometry::SlopeSE => sx >= flip,
+        Geometry::SlopeNW => sx <= flip,
+        Geometry::SlopeNE => sx >= sy,
+    };
+
+    filled.then_some([40, 40, 45])
+}
+
+fn feature_color(tile: &Tile) -> Option<[u8; 3]> {
+    if tile.is_shortcut_entrance() {
+        return Some([255, 255, 255])
+    }
+
+    tile.features().iter().find_map(|feature| match feature {
+        Feature::RoomEntrance => Some([230, 60, 60]),
+        Feature::CreatureDen => Some([230, 140, 30]),
+        Feature::ScavengerHole | Feature::GopherHole => Some([160, 90, 200]),
+        Feature::ShortcutPathDot => Some([250, 250, 250]),
+        Feature::HPole | Feature::VPole => Some([120, 90, 60]),
+        Feature::WormGrass => Some([200, 40, 40]),
+        Feature::Hive => Some([90, 160, 70]),
+        Feature::Waterfall => Some([60, 120, 220]),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rwlevel::fixture;
+
+    use super::{ascii, ppm};
+
+    #[test]
+    fn test_ascii_matches_fixture() {
+        let rows = [
+            "#E####",
+            "#o|-+#",
+            "#>.=G#",
+            "#DIw/\\",
+        ];
+        let level = fixture::level(&rows);
+
+        let rendered = ascii(&level, 0);
+        assert_eq!(rendered.lines().collect::<Vec<_>>(), rows);
+
+        let image = ppm(&level, 4);
+        assert!(image.starts_with(b"P6\n24 16\n255\n"));
+        assert_eq!(image.len(), "P6\n24 16\n255\n".len() + 24 * 16 * 3);
+    }
+}
@@ -1,33 +1,75 @@
 pub mod lingo_to_json;
-mod lingo_dsl;
+pub mod lingo_dsl;
+mod json_to_lingo;
+mod geometry;
+mod deferred;
+mod sections;
+mod binary;
+mod query;
+pub(crate) mod canonical;
+#[cfg(test)]
+pub(crate) mod fixture;
 
-use std::{array, path::Path};
+use std::{fs, io, path::Path};
 
+use deferred::Deferred;
+use geometry::Layers;
 use lingo_dsl::Point;
-use lingo_to_json::{read_to_struct, BetterIndexing, ProjectJson};
-use serde_json::Value;
+use lingo_to_json::{parse_line, ProjectJson};
+pub use sections::{Section, Sections};
+use sections::{LazyProject, Line};
+use serde_json::{json, Value};
 
-use crate::{effect::Effect, tile::{Feature, Geometry, Tile}};
+use crate::{effect::Effect, tile::{Tile, TileLayer, TileMut}};
 
+/// (Layer, (X, Y)) of a tile
+type TilePos = (usize, (usize, usize));
+
+/// With the `serde` feature, a level is serialized as its name, its tiles and
+/// the rest of its project file as text
 #[allow(unused)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct RWLevel {
     /// Based off the filename provided to RWLevel::load
     name: String,
     /// Room settings such as dimensions and medium
-    meta: RWLevelMeta,
-    /// Tiles composing the room
-    /// Each index represents a layer of the level, starting with the foreground at index 0.
-    /// Inner vectors are 2D arrays projected into 1D, progressing from Y = 0 to height for each X index
-    tiles: [Vec<Tile>; 3],
-    effects: Vec<Effect>,
+    meta: Deferred<RWLevelMeta>,
+    /// Tiles composing the room, one layer at a time starting with the foreground at index 0
+    tiles: Deferred<Layers>,
+    effects: Deferred<Vec<Effect>>,
+    /// Project as it was loaded, used to write back everything which isn't modelled above.
+    /// The geometry line is always rebuilt from `tiles`, so it isn't kept
+    project: LazyProject,
 }
 
 #[allow(unused)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RWLevelMeta {
     /// (Width, Height) dimensions of the level
     dimensions: (usize, usize),
+    /// (Left, Top, Right, Bottom) widths of the border outside the playable area
+    extra_tiles: (usize, usize, usize, usize),
+    /// Read from the water line the first time it's needed
+    water: Deferred<Water>,
+    /// Top-left corner of each camera, in editor pixels (20 per tile).
+    /// Read from the cameras line the first time it's needed
+    cameras: Deferred<Vec<Point>>,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Water {
+    /// Water level in tiles, counted up from the bottom of the level. -1 if the room has no water
+    level: isize,
+    /// Whether the water is drawn in front of layer 1
+    in_front: bool,
+}
+
+impl Default for Water {
+    fn default() -> Self {
+        Self { level: -1, in_front: false }
+    }
 }
 
 impl RWLevel {
@@ -39,104 +81,430 @@ impl RWLevel {
     pub const L3_BG: usize = 2;
 
     pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
-        let json = read_to_struct(&path)?;
+        Self::load_sections(path, Sections::ALL)
+    }
+
+    /// Load a level, parsing only `sections` of the project file up front.
+    /// The others are parsed the first time they're needed, see [RWLevel::from_project_str_sections]
+    pub fn load_sections<P: AsRef<Path>>(path: P, sections: Sections) -> Option<Self> {
+        let contents = fs::read_to_string(&path).ok()?;
 
         let name = path.as_ref()
             .to_path_buf()
             .file_stem()
             .and_then(|ostr| ostr.to_owned().into_string().ok())?;
 
-        let size: Point = json._settings2.get("#size")
-                .and_then(Value::as_str)
-                .and_then(|val| val.parse().ok())
-                .unwrap();
+        Self::from_project_str_sections(&name, &contents, sections)
+    }
+
+    /// Load a level from the contents of a project file rather than a path.
+    /// The geometry line is parsed straight into the tiles, without going through JSON
+    pub fn from_project_str(name: &str, contents: &str) -> Option<Self> {
+        Self::from_project_str_sections(name, contents, Sections::ALL)
+    }
 
-        let meta = RWLevelMeta {
-            dimensions: (size.fst as usize, size.snd as usize)
+    /// [RWLevel::from_project_str], parsing only `sections` up front.
+    /// Asking for the geometry also parses the settings, which hold the dimensions.
+    ///
+    /// The other sections are parsed the first time they're needed. If they're bad,
+    /// [RWLevel::try_meta] and [RWLevel::try_effects] give None, [RWLevel::tile]
+    /// and [RWLevel::layer] give None, and the water and cameras read as missing
+    pub fn from_project_str_sections(name: &str, contents: &str, sections: Sections) -> Option<Self> {
+        let (geom, project) = LazyProject::split(contents, sections)?;
+        let mut level = Self {
+            name: name.to_owned(),
+            meta: Deferred::pending(),
+            tiles: Deferred::raw(geom),
+            effects: Deferred::pending(),
+            project,
         };
-        
-        let tiles = load_tiles(&json, &meta);
-        let effects = load_effects(&json);
+
+        if sections.contains(Section::Settings) || sections.contains(Section::Geometry) {
+            level.meta.try_get_mut(|_| load_meta(&level.project))?;
+        }
+        if sections.contains(Section::Geometry) {
+            let dimensions = level.meta().dimensions;
+            level.tiles.try_get_mut(|raw| geometry::parse(raw, dimensions))?;
+        }
+        if sections.contains(Section::Effects) {
+            level.effects.try_get_mut(|_| load_effects(level.project.try_line(Line::Effects)?))?;
+        }
+
+        Some(level)
+    }
+
+    /// Build a level from its project as JSON, or None if the settings, geometry or effects are bad
+    pub(crate) fn from_project(name: String, json: ProjectJson) -> Option<Self> {
+        let mut json = json;
+        let geom = json._geom.take();
+        let project = LazyProject::parsed(json);
+
+        let meta = load_meta(&project)?;
+        let tiles = geometry::from_json(&geom, meta.dimensions)?;
+        let effects = load_effects(project.try_line(Line::Effects)?)?;
 
         Some(Self {
             name,
-            meta,
-            tiles,
-            effects,
+            meta: Deferred::parsed(meta),
+            tiles: Deferred::parsed(tiles),
+            effects: Deferred::parsed(effects),
+            project,
         })
     }
-}
 
-fn load_tiles(json: &ProjectJson, meta: &RWLevelMeta) -> [Vec<Tile>; 3] {
-    let dim = meta.dimensions.0 * meta.dimensions.1;
-    let w = meta.dimensions.0;
-    let mut tiles = array::from_fn(|_| vec![Tile::default(); dim]);
-
-    for x in 0..meta.dimensions.0 {
-        for y in 0..meta.dimensions.1 {
-            for layer in 0..3 {
-                let tile = json._geom.index(&[&x, &y, &layer])
-                    .and_then(|v| v.as_array())
-                    .expect("Bad tile entry in level");
-
-                let _geom_json = tile.get(0).and_then(|v| v.as_u64()).expect("Bad geometry entry in level") as u8;
-
-                let geometry: Geometry = Geometry::from_data(_geom_json).expect("Bad geometry type");
-                let features: Vec<Feature> = tile.get(1)
-                    .and_then(|v| v.as_array())
-                    .expect("Bad feature entry in level")
-                    .iter()
-                    .filter_map(|val| val.as_u64())
-                    .filter_map(|val| val.try_into().ok())
-                    .filter_map(Feature::from_data)
-                    .collect();
-
-                tiles[layer][y * w + x] = Tile {
-                    geometry,
-                    features,
-                };
+    /// Write the level back out in the level editor's project format, with the
+    /// editor's UI state reset as by [RWLevel::canonicalize]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut project = self.to_project();
+        canonical::canonicalize(&mut project, ProjectJson::line_mut);
+        fs::write(path, json_to_lingo::write_lines(&project))
+    }
+
+    /// The level in the level editor's project format
+    pub fn to_project_string(&self) -> String {
+        json_to_lingo::write_lines(&self.to_project())
+    }
+
+    /// The project converted to JSON, one key per line of the project file.
+    /// This is the editor's own representation and varies between editor versions
+    pub fn to_raw_json(&self) -> Value {
+        let json = self.to_project();
+        json!({
+            "geometry": json._geom,
+            "tiles": json._tiles,
+            "effects": json._effects,
+            "lights": json._lights,
+            "settings": json._settings1,
+            "project": json._settings2,
+            "cameras": json._cams,
+            "water": json._water,
+            "props": json._props,
+        })
+    }
+
+    /// Change the dimensions of the level, keeping the top-left corner in place.
+    /// Added tiles are solid wall on every layer, added effect cells are 0,
+    /// and added cells of the tile editor's matrix are empty.
+    /// Panics if the geometry or effects weren't loaded up front and are bad
+    pub fn resize(&mut self, w: usize, h: usize) {
+        for layer in self.layers_mut().expect("Bad geometry in level") {
+            layer.resize(w, h, Tile::default());
+        }
+
+        for effect in self.effects_mut().iter_mut() {
+            effect.matrix.resize(w, h, 0.0);
+        }
+
+        if let Some(Value::Array(columns)) = self.project.line_mut(Line::Tiles).get_mut("#tlMatrix") {
+            let empty = json!([
+                {"#tp": "default", "#Data": 0},
+                {"#tp": "default", "#Data": 0},
+                {"#tp": "default", "#Data": 0},
+            ]);
+
+            columns.resize(w, Value::Array(vec![empty.clone(); h]));
+            for column in columns.iter_mut() {
+                if let Value::Array(cells) = column {
+                    cells.resize(h, empty.clone());
+                }
             }
         }
+
+        self.meta_mut().dimensions = (w, h);
+    }
+
+    /// Fold the modelled state back into the loaded project
+    pub(crate) fn to_project(&self) -> ProjectJson {
+        let mut json = self.project_without_geometry();
+        json._geom = self.geometry_json(TileLayer::tile_json);
+        json
+    }
+
+    /// [RWLevel::to_project] with the geometry line left null
+    fn project_without_geometry(&self) -> ProjectJson {
+        let mut json = self.project.to_json();
+        let meta = self.meta();
+        let (w, h) = meta.dimensions;
+
+        if let Some(effects) = json._effects.get_mut("#effects") {
+            *effects = self.effects().iter().map(Effect::to_json).collect();
+        }
+
+        if let Some(water) = json._water.as_object_mut() {
+            water.insert("#waterLevel".into(), meta.water_level().into());
+            water.insert("#waterInFront".into(), (meta.water_in_front() as u8).into());
+        }
+
+        if let Some(cams) = json._cams.get_mut("#cameras") {
+            *cams = meta.cameras().iter()
+                .map(|cam| Value::String(format!("point({}, {})", cam.fst, cam.snd)))
+                .collect();
+        }
+
+        if let Some(size) = json._settings2.get_mut("#size") {
+            *size = format!("point({w}, {h})").into();
+        }
+
+        json
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The settings. Panics if they weren't loaded up front and are bad, see [RWLevel::try_meta]
+    pub fn meta(&self) -> &RWLevelMeta {
+        self.meta.get("settings", |_| load_meta(&self.project))
+    }
+
+    /// The settings, or None if they weren't loaded up front and are bad
+    pub fn try_meta(&self) -> Option<&RWLevelMeta> {
+        self.meta.try_get(|_| load_meta(&self.project))
+    }
+
+    fn meta_mut(&mut self) -> &mut RWLevelMeta {
+        let project = &self.project;
+        self.meta.get_mut("settings", |_| load_meta(project))
+    }
+
+    /// None if the geometry or settings weren't loaded up front and are bad
+    fn layers(&self) -> Option<&Layers> {
+        let dimensions = self.try_meta()?.dimensions;
+        self.tiles.try_get(|raw| geometry::parse(raw, dimensions))
+    }
+
+    fn layers_mut(&mut self) -> Option<&mut Layers> {
+        let dimensions = self.try_meta()?.dimensions;
+        self.tiles.try_get_mut(|raw| geometry::parse(raw, dimensions))
+    }
+
+    /// The geometry line, `[x][y][layer] = [geometry, [features]]`, with each tile written by `tile_json`
+    pub(crate) fn geometry_json(&self, tile_json: impl Fn(&TileLayer, usize, usize) -> Option<Value>) -> Value {
+        let (w, h) = self.meta().dimensions;
+        let layers = self.layers().expect("Bad geometry in level");
+
+        Value::Array((0..w)
+            .map(|x| Value::Array((0..h)
+                .map(|y| Value::Array(layers.iter()
+                    .map(|layer| tile_json(layer, x, y).expect("Layers match the level's dimensions"))
+                    .collect()))
+                .collect()))
+            .collect())
     }
 
-    tiles
+    /// Get the tile at (x, y) on the given layer, or None if out of bounds or the geometry is bad.
+    /// Layers keep geometry and features apart, so this is a copy, see [RWLevel::tile_mut] for editing
+    pub fn tile(&self, layer: usize, x: usize, y: usize) -> Option<Tile> {
+        self.layer(layer)?.get(x, y)
+    }
+
+    /// Get the tile at (x, y) on the given layer for editing, or None if out of bounds or the geometry is bad.
+    /// Changes are written back to the level when the [TileMut] is dropped
+    pub fn tile_mut(&mut self, layer: usize, x: usize, y: usize) -> Option<TileMut<'_>> {
+        self.layers_mut()?.get_mut(layer)?.get_mut(x, y)
+    }
+
+    /// All tiles of a layer, or None if there's no such layer or the geometry is bad
+    pub fn layer(&self, layer: usize) -> Option<&TileLayer> {
+        self.layers()?.get(layer)
+    }
+
+    /// The effects. Panics if they weren't loaded up front and are bad, see [RWLevel::try_effects]
+    pub fn effects(&self) -> &[Effect] {
+        self.effects.get("effects", |_| load_effects(self.project.try_line(Line::Effects)?))
+    }
+
+    /// The effects, or None if they weren't loaded up front and are bad
+    pub fn try_effects(&self) -> Option<&[Effect]> {
+        self.effects.try_get(|_| load_effects(self.project.try_line(Line::Effects)?)).map(Vec::as_slice)
+    }
+
+    fn effects_mut(&mut self) -> &mut Vec<Effect> {
+        let project = &self.project;
+        self.effects.get_mut("effects", |_| load_effects(project.try_line(Line::Effects)?))
+    }
+
+    /// (Layer, (X, Y)) of tiles which listed a feature more than once in the project
+    pub(crate) fn duplicate_features(&self) -> Vec<TilePos> {
+        self.layers()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .flat_map(|(layer, tiles)| tiles.duplicated().map(move |pos| (layer, pos)))
+            .collect()
+    }
+
+    /// List each feature of a tile only once when the level is written
+    pub(crate) fn dedup_features(&mut self) {
+        for layer in self.layers_mut().into_iter().flatten() {
+            layer.dedup_listed();
+        }
+    }
+}
+
+impl RWLevelMeta {
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.dimensions
+    }
+
+    pub fn extra_tiles(&self) -> (usize, usize, usize, usize) {
+        self.extra_tiles
+    }
+
+    /// Water level in tiles, counted up from the bottom of the level. -1 if the room has no water
+    pub fn water_level(&self) -> isize {
+        self.water().level
+    }
+
+    /// Whether the water is drawn in front of layer 1
+    pub fn water_in_front(&self) -> bool {
+        self.water().in_front
+    }
+
+    /// Top-left corner of each camera, in editor pixels (20 per tile)
+    pub fn cameras(&self) -> &[Point] {
+        self.cameras.try_get(|raw| Some(load_cameras(&parse_line(raw)?))).map_or(&[], Vec::as_slice)
+    }
+
+    /// A bad water line reads as no water
+    fn water(&self) -> Water {
+        self.water.try_get(|raw| Some(load_water(&parse_line(raw)?))).copied().unwrap_or_default()
+    }
+}
+
+/// None if the line holding the settings is bad, or the size is missing.
+/// The water and cameras are read from their lines when first needed
+fn load_meta(project: &LazyProject) -> Option<RWLevelMeta> {
+    let settings = project.try_line(Line::Settings2)?;
+
+    let size: Point = settings.get("#size")
+            .and_then(Value::as_str)
+            .and_then(|val| val.parse().ok())?;
+
+    Some(RWLevelMeta {
+        dimensions: (usize::try_from(size.fst).ok()?, usize::try_from(size.snd).ok()?),
+        extra_tiles: load_extra_tiles(settings),
+        water: project.read(Line::Water, load_water),
+        cameras: project.read(Line::Cameras, load_cameras),
+    })
+}
+
+fn load_water(water: &Value) -> Water {
+    Water {
+        level: water.get("#waterLevel")
+            .and_then(Value::as_i64)
+            .unwrap_or(-1) as isize,
+        in_front: water.get("#waterInFront")
+            .and_then(Value::as_i64)
+            .is_some_and(|v| v != 0),
+    }
 }
 
-fn load_effects(json: &ProjectJson) -> Vec<Effect> {
-    let mut effects = Vec::new();
-    let Some(arr) = json._effects.get("#effects")
+fn load_extra_tiles(settings: &Value) -> (usize, usize, usize, usize) {
+    let extra: Vec<usize> = settings.get("#extraTiles")
+        .and_then(Value::as_array)
+        .map(|arr| arr.iter()
+            .filter_map(Value::as_u64)
+            .map(|v| v as usize)
+            .collect())
+        .unwrap_or_default();
+
+    match extra[..] {
+        [left, top, right, bottom] => (left, top, right, bottom),
+        _ => (0, 0, 0, 0),
+    }
+}
+
+fn load_cameras(cams: &Value) -> Vec<Point> {
+    cams.get("#cameras")
+        .and_then(Value::as_array)
+        .map(|cams| cams.iter()
+            .filter_map(Value::as_str)
+            .filter_map(|cam| cam.parse().ok())
+            .collect())
+        .unwrap_or_default()
+}
+
+/// None if any entry of `#effects` is bad
+fn load_effects(line: &Value) -> Option<Vec<Effect>> {
+    let Some(arr) = line.get("#effects")
         .and_then(|v| v.as_array())
     else {
-        return effects;
+        return Some(vec![]);
     };
 
-    for entry in arr {
-        let name = entry.get("#nm")
-            .and_then(|v| v.as_str())
-            .expect("Missing effect name #nm")
-            .to_owned();
-        let mtrx = entry.get("#mtrx")
-            .and_then(|v| v.as_array())
-            .expect("Missing #mtrx");
-        let opts = entry.get("#Options")
-            .expect("Missing #Options");
-
-        let matrix = mtrx.iter()
-            .map(|v_arr| {
-                let pctgs = v_arr.as_array().expect("Array is bad!");
-
-                pctgs.iter()
-                    .filter_map(|entry| entry.as_f64())
-                    .collect()
-            })
-            .collect();
-
-        effects.push(Effect {
+    arr.iter().map(Effect::from_entry).collect()
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RWLevel {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(serde::Serialize)]
+        struct Repr<'a> {
+            name: &'a str,
+            tiles: &'a Layers,
+            project: String,
+        }
+
+        let tiles = self.layers().ok_or_else(|| serde::ser::Error::custom("bad geometry in level"))?;
+        let project = json_to_lingo::write_lines(&self.project_without_geometry());
+        Repr { name: &self.name, tiles, project }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RWLevel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            name: String,
+            tiles: Layers,
+            project: String,
+        }
+
+        let Repr { name, tiles, project } = Repr::deserialize(deserializer)?;
+        let (_, project) = LazyProject::split(&project, Sections::ALL).ok_or_else(|| D::Error::custom("bad project"))?;
+        let meta = load_meta(&project).ok_or_else(|| D::Error::custom("bad settings in level"))?;
+        let effects = load_effects(project.line(Line::Effects)).ok_or_else(|| D::Error::custom("bad effects in level"))?;
+        if tiles.iter().any(|layer| layer.dimensions() != meta.dimensions) {
+            return Err(D::Error::custom("tiles don't match the dimensions of the level"))
+        }
+
+        Ok(Self {
             name,
-            matrix,
-            options: opts.clone(),
-        });
+            meta: Deferred::parsed(meta),
+            tiles: Deferred::parsed(tiles),
+            effects: Deferred::parsed(effects),
+            project,
+        })
     }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::{fixture::{self, Edit}, RWLevel};
 
-    effects
+    #[test]
+    fn test_serde_round_trip() {
+        let project = fixture::project(&[
+            "#E##",
+            "#o|#",
+            "#>.w",
+        ]).edit("#effects: []", r#"#effects: [[#nm: "Slime", #mtrx: [[0, 0, 0], [0, 50, 0], [0, 0, 0], [0, 0, 100]], #Options: [["Layers", ["All", "1", "2", "3"], "All"]]]]"#);
+        let level = RWLevel::from_project_str("TEST", &project).unwrap();
+
+        let json = serde_json::to_string(&level).unwrap();
+        let back: RWLevel = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_project_string(), level.to_project_string());
+        assert_eq!(back.tile(RWLevel::L1_FG, 1, 2), level.tile(RWLevel::L1_FG, 1, 2));
+
+        // Formats which don't describe their own types can't hold JSON values
+        let bytes = bincode::serialize(&level).unwrap();
+        let back: RWLevel = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.to_project_string(), level.to_project_string());
+        assert_eq!(back.effects()[0].matrix(), level.effects()[0].matrix());
+        assert_eq!(back.effects()[0].options(), level.effects()[0].options());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,246 @@
+//! A compact binary form of a level, for caching parsed projects.
+//!
+//! After a magic number and the format version come the name, every line of
+//! the project but the geometry as tagged values, the tiles as one geometry
+//! byte and one feature bitset per tile, and the tiles which listed their
+//! features out of code order with the features as listed.
+//! Integers are little-endian, lengths are `u32`.
+
+use std::array;
+
+use serde_json::{Map, Number, Value};
+
+use crate::tile::{Feature, FeatureSet, Geometry, Tile, TileLayer};
+
+use super::{
+    deferred::Deferred,
+    geometry::Layers,
+    lingo_to_json::ProjectJson,
+    load_effects, load_meta,
+    sections::{LazyProject, Line},
+    RWLevel,
+};
+
+const MAGIC: &[u8; 4] = b"RWLV";
+
+impl RWLevel {
+    /// Bumped whenever the binary form changes, old data is then rejected
+    pub const BINARY_VERSION: u32 = 2;
+
+    /// The level in a compact binary form, read back with [RWLevel::from_bytes]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Writer(Vec::new());
+        out.0.extend_from_slice(MAGIC);
+        out.u32(Self::BINARY_VERSION);
+        out.str(&self.name);
+
+        let mut project = self.to_project();
+        for line in &Line::ALL[1..] {
+            out.value(project.line_mut(*line));
+        }
+
+        let layers = self.layers().expect("Bad geometry in level");
+        for layer in layers {
+            for (_, _, tile) in layer.iter() {
+                out.0.push(tile.geometry as u8);
+                out.u32(tile.features.bits());
+            }
+        }
+
+        for layer in layers {
+            out.len(layer.all_listed().len());
+            for ((x, y), features) in layer.all_listed() {
+                out.len(*x);
+                out.len(*y);
+                out.len(features.len());
+                out.0.extend(features.iter().map(|&feature| feature as u8));
+            }
+        }
+
+        out.0
+    }
+
+    /// Read a level written by [RWLevel::to_bytes]. None if the data is
+    /// malformed or from another version
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut input = Reader(bytes);
+        if input.take(4)? != MAGIC || input.u32()? != Self::BINARY_VERSION {
+            return None
+        }
+        let name = input.str()?.to_owned();
+
+        let mut json = ProjectJson::empty();
+        for line in &Line::ALL[1..] {
+            *json.line_mut(*line) = input.value()?;
+        }
+        let project = LazyProject::parsed(json);
+
+        let meta = load_meta(&project)?;
+        let tiles = read_tiles(&mut input, meta.dimensions)?;
+        let effects = load_effects(project.line(Line::Effects))?;
+        if !input.0.is_empty() {
+            return None
+        }
+
+        Some(Self {
+            name,
+            meta: Deferred::parsed(meta),
+            tiles: Deferred::parsed(tiles),
+            effects: Deferred::parsed(effects),
+            project,
+        })
+    }
+}
+
+fn read_tiles(input: &mut Reader<'_>, (w, h): (usize, usize)) -> Option<Layers> {
+    // A geometry byte and a bitset for each tile of each layer
+    if w.checked_mul(h)?.checked_mul(3 * 5)? > input.0.len() {
+        return None
+    }
+
+    let mut layers: Layers = array::from_fn(|_| TileLayer::new(w, h));
+    for layer in &mut layers {
+        for y in 0..h {
+            for x in 0..w {
+                let geometry = Geometry::from_data(input.u8()?)?;
+                let features = FeatureSet::from_bits(input.u32()?)?;
+                *layer.get_mut(x, y)? = Tile { geometry, features };
+            }
+        }
+    }
+
+    for layer in &mut layers {
+        // A position and the number of features at least
+        for _ in 0..input.count(4 + 4 + 4)? {
+            let (x, y) = (input.len()?, input.len()?);
+            if x >= w || y >= h {
+                return None
+            }
+
+            let features = (0..input.count(1)?)
+                .map(|_| Feature::from_data(input.u8()?))
+                .collect::<Option<_>>()?;
+            layer.set_listed((x, y), features);
+        }
+    }
+
+    Some(layers)
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len.try_into().expect("Length doesn't fit in 32 bits"));
+    }
+
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.0.extend_from_slice(s.as_bytes());
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.0.push(0),
+            Value::Bool(b) => self.0.push(1 + *b as u8),
+            Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    self.0.push(3);
+                    self.0.extend_from_slice(&i.to_le_bytes());
+                } else if let Some(u) = n.as_u64() {
+                    self.0.push(4);
+                    self.0.extend_from_slice(&u.to_le_bytes());
+                } else {
+                    self.0.push(5);
+                    self.0.extend_from_slice(&n.as_f64().unwrap_or_default().to_le_bytes());
+                }
+            }
+            Value::String(s) => {
+                self.0.push(6);
+                self.str(s);
+            }
+            Value::Array(items) => {
+                self.0.push(7);
+                self.len(items.len());
+                for item in items {
+                    self.value(item);
+                }
+            }
+            Value::Object(map) => {
+                self.0.push(8);
+                self.len(map.len());
+                for (key, item) in map {
+                    self.str(key);
+                    self.value(item);
+                }
+            }
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let (head, rest) = self.0.split_at_checked(n)?;
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        self.u32().map(|len| len as usize)
+    }
+
+    /// The number of items which follow, each taking at least `size` bytes.
+    /// None if there aren't that many bytes left, so that corrupt data can't
+    /// ask for a huge allocation
+    fn count(&mut self, size: usize) -> Option<usize> {
+        let count = self.len()?;
+        (count.checked_mul(size)? <= self.0.len()).then_some(count)
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        let len = self.len()?;
+        std::str::from_utf8(self.take(len)?).ok()
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        Some(match self.u8()? {
+            0 => Value::Null,
+            1 => Value::Bool(false),
+            2 => Value::Bool(true),
+            3 => i64::from_le_bytes(self.array()?).into(),
+            4 => u64::from_le_bytes(self.array()?).into(),
+            5 => Value::Number(Number::from_f64(f64::from_le_bytes(self.array()?))?),
+            6 => self.str()?.into(),
+            7 => Value::Array((0..self.count(1)?).map(|_| self.value()).collect::<Option<_>>()?),
+            8 => {
+                // A key's length and a value's tag at least
+                let len = self.count(4 + 1)?;
+                let mut map = Map::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.str()?.to_owned();
+                    map.insert(key, self.value()?);
+                }
+                Value::Object(map)
+            }
+            _ => return None,
+        })
+    }
+}
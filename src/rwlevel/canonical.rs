@@ -0,0 +1,118 @@
+//! Editor state which is saved in project files but isn't part of the level.
+//! The editor rewrites it every time a project is opened, so version-controlled
+//! projects change even when the level doesn't.
+
+use serde_json::Value;
+
+use super::{lingo_to_json::ProjectJson, sections::{LazyProject, Line}, RWLevel};
+
+/// Keys of each line of the project holding the state of the editor's UI,
+/// such as the last keys pressed, the cursor or the selected tool
+pub(crate) const EDITOR_STATE: [(&str, &[&str]); 6] = [
+    ("tiles", &["#lastKeys", "#Keys", "#workLayer", "#lstMsPs", "#toolType", "#toolData", "#tmPos", "#tmSavPosL", "#specialEdit"]),
+    ("effects", &["#lastKeys", "#Keys", "#lstMsPs", "#emPos", "#editEffect", "#selectEditEffect", "#mode", "#brushSize"]),
+    ("light", &["#pos", "#rot", "#sz", "#col", "#Keys", "#lastKeys", "#lastTm", "#paintShape"]),
+    ("render", &["#mouse", "#lastMouse", "#mouseClick"]),
+    ("cameras", &["#selectedCamera", "#Keys", "#lastKeys"]),
+    ("props", &[
+        "#lastKeys", "#Keys", "#workLayer", "#lstMsPs", "#pmPos", "#pmSavPosL", "#propRotation",
+        "#propStretchX", "#propStretchY", "#propFlipX", "#propFlipY", "#depth", "#color",
+    ]),
+];
+
+/// Whether `key` of the line is editor state. Lingo symbols are case insensitive
+pub(crate) fn is_editor_state(line: &str, key: &str) -> bool {
+    EDITOR_STATE.iter().any(|(l, keys)| *l == line && keys.iter().any(|k| k.eq_ignore_ascii_case(key)))
+}
+
+fn line(name: &str) -> Line {
+    match name {
+        "tiles" => Line::Tiles,
+        "effects" => Line::Effects,
+        "light" => Line::Lights,
+        "render" => Line::Settings2,
+        "cameras" => Line::Cameras,
+        "props" => Line::Props,
+        _ => unreachable!("Unknown project line {name}"),
+    }
+}
+
+impl RWLevel {
+    /// Reset the editor's UI state to that of a new project, so that saving the
+    /// level only changes the file when the level itself has changed.
+    /// Keys which the project doesn't have are left out
+    pub fn canonicalize(&mut self) {
+        canonicalize(&mut self.project, LazyProject::line_mut);
+    }
+
+    /// Whether the editor's UI state is already reset
+    pub fn is_canonical(&self) -> bool {
+        let mut canonical = self.clone();
+        canonical.canonicalize();
+        canonical.to_project_string() == self.to_project_string()
+    }
+}
+
+/// [RWLevel::canonicalize] for any form of a project, given how to get at its lines
+pub(super) fn canonicalize<P>(project: &mut P, line_mut: fn(&mut P, Line) -> &mut Value) {
+    let mut template = ProjectJson::template();
+    for (name, keys) in EDITOR_STATE {
+        reset(line_mut(project, line(name)), template.line_mut(line(name)), keys);
+    }
+}
+
+/// Set each of `keys` the line has to its value in `defaults`, matching keys
+/// case insensitively and keeping the line's spelling of them
+fn reset(line: &mut Value, defaults: &Value, keys: &[&str]) {
+    let Some(map) = line.as_object_mut() else {
+        return
+    };
+
+    for (key, value) in map.iter_mut() {
+        let default = keys.iter()
+            .find(|k| k.eq_ignore_ascii_case(key))
+            .and_then(|k| defaults.get(*k));
+        if let Some(default) = default {
+            *value = default.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture::{self, Edit};
+
+    #[test]
+    fn test_canonicalize() {
+        let project = fixture::project(&["#.#"])
+            .edit("#emPos: point(1, 1), #editEffect: 0", "#emPos: point(7, 3), #editEffect: 2")
+            .edit("#lastKeys: [], #Keys: [], #workLayer: 1", r#"#lastKeys: [#L: 1], #Keys: [#L: 0], #workLayer: 2"#);
+        let mut level = super::RWLevel::from_project_str("TEST", &project).unwrap();
+        assert!(!level.is_canonical());
+
+        // Saving resets the state too, without changing the level in memory
+        let path = std::env::temp_dir().join(format!("rwlevel-canonical-{}.txt", std::process::id()));
+        level.save(&path).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved, fixture::project(&["#.#"]).replace('\n', "\r"));
+        assert!(!level.is_canonical());
+
+        level.canonicalize();
+        assert!(level.is_canonical());
+        assert_eq!(level.to_project_string(), saved);
+    }
+
+    #[test]
+    fn test_canonicalize_case() {
+        let project = fixture::project(&["#.#"]);
+        let shouted = project.replacen("#lastKeys: [], #Keys: []", "#LASTKEYS: [#L: 1], #keys: [#L: 0]", 1);
+        assert_ne!(shouted, project);
+
+        let mut level = super::RWLevel::from_project_str("TEST", &shouted).unwrap();
+        assert!(!level.is_canonical());
+        level.canonicalize();
+        assert!(level.is_canonical());
+        assert_eq!(level.to_project_string(), shouted.replacen("[#L: 1], #keys: [#L: 0]", "[], #keys: []", 1).replace('\n', "\r"));
+    }
+}
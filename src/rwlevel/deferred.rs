@@ -0,0 +1,87 @@
+use once_cell::sync::OnceCell;
+
+/// Part of a level which is parsed on first use, either from its text in the
+/// project file or from other parts of the level
+#[derive(Clone, Debug)]
+pub(super) struct Deferred<T> {
+    /// Text left to parse, dropped once the value is modified
+    raw: Option<Box<str>>,
+    value: OnceCell<T>,
+}
+
+impl<T> Deferred<T> {
+    pub(super) fn parsed(value: T) -> Self {
+        Self { raw: None, value: OnceCell::with_value(value) }
+    }
+
+    pub(super) fn raw(text: &str) -> Self {
+        Self { raw: Some(text.into()), value: OnceCell::new() }
+    }
+
+    /// Nothing to parse, the value is built from other parts of the level
+    pub(super) fn pending() -> Self {
+        Self { raw: None, value: OnceCell::new() }
+    }
+
+    /// A value read from this one: now if this is parsed, otherwise from the
+    /// same text on first use
+    pub(super) fn map<U>(&self, f: impl FnOnce(&T) -> U) -> Deferred<U> {
+        match self.value.get() {
+            Some(value) => Deferred::parsed(f(value)),
+            None => Deferred { raw: self.raw.clone(), value: OnceCell::new() },
+        }
+    }
+
+    /// Parse now, if not already parsed
+    pub(super) fn try_get(&self, parse: impl FnOnce(&str) -> Option<T>) -> Option<&T> {
+        self.value.get_or_try_init(|| parse(self.raw.as_deref().unwrap_or_default()).ok_or(())).ok()
+    }
+
+    /// Parse now, if not already parsed. Panics with `what` if the text is bad
+    pub(super) fn get(&self, what: &str, parse: impl FnOnce(&str) -> Option<T>) -> &T {
+        self.try_get(parse).unwrap_or_else(|| panic!("Bad {what} in level"))
+    }
+
+    /// Parse now, if not already parsed, and drop the text
+    pub(super) fn try_get_mut(&mut self, parse: impl FnOnce(&str) -> Option<T>) -> Option<&mut T> {
+        self.try_get(parse)?;
+        self.raw = None;
+        self.value.get_mut()
+    }
+
+    pub(super) fn get_mut(&mut self, what: &str, parse: impl FnOnce(&str) -> Option<T>) -> &mut T {
+        self.try_get_mut(parse).unwrap_or_else(|| panic!("Bad {what} in level"))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Deferred<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(serde::Serialize)]
+        enum Repr<'a, T> {
+            Parsed(&'a T),
+            Raw(Option<&'a str>),
+        }
+
+        match self.value.get() {
+            Some(value) => Repr::Parsed(value),
+            None => Repr::Raw(self.raw.as_deref()),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Deferred<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        enum Repr<T> {
+            Parsed(T),
+            Raw(Option<Box<str>>),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Parsed(value) => Self::parsed(value),
+            Repr::Raw(raw) => Self { raw, value: OnceCell::new() },
+        })
+    }
+}
@@ -0,0 +1,94 @@
+//! Builds small project files from ASCII maps for use in tests.
+//!
+//! Each char of a row is one layer 1 tile:
+//!
+//! | Char | Geometry | Features |
+//! |------|----------|----------|
+//! | `#` | Wall | |
+//! | `.` | Air | |
+//! | `=` | Floor | |
+//! | `/` | SlopeSE | |
+//! | `\` | SlopeSW | |
+//! | `G` | Glass | |
+//! | `>` | ShortcutEntrance | ShortcutEntrance |
+//! | `o` | Wall | ShortcutPathDot |
+//! | `E` | Wall | RoomEntrance |
+//! | `D` | Wall | CreatureDen |
+//! | `S` | Wall | ScavengerHole |
+//! | `M` | Wall | GopherHole |
+//! | `\|` | Air | VPole |
+//! | `-` | Air | HPole |
+//! | `+` | Air | HPole, VPole |
+//! | `I` | Wall | VPole |
+//! | `w` | Air | WormGrass |
+//! | `H` | Air | Hive |
+//!
+//! Layers 2 and 3 are left as air.
+
+use super::{lingo_to_json::TEMPLATE, RWLevel};
+
+fn cell(c: char) -> (u8, &'static str) {
+    match c {
+        '#' => (1, ""),
+        '.' => (0, ""),
+        '=' => (6, ""),
+        '/' => (3, ""),
+        '\\' => (2, ""),
+        'G' => (9, ""),
+        '>' => (7, "4"),
+        'o' => (1, "5"),
+        'E' => (1, "6"),
+        'D' => (1, "7"),
+        'S' => (1, "21"),
+        'M' => (1, "19"),
+        '|' => (0, "2"),
+        '-' => (0, "1"),
+        '+' => (0, "1, 2"),
+        'I' => (1, "2"),
+        'w' => (0, "20"),
+        'H' => (0, "3"),
+        _ => panic!("Unknown fixture tile {c:?}"),
+    }
+}
+
+/// Project file contents for the given layer 1 map, using `\n` line endings
+pub(crate) fn project(rows: &[&str]) -> String {
+    let h = rows.len();
+    let w = rows[0].chars().count();
+    let grid: Vec<Vec<char>> = rows.iter().map(|row| row.chars().collect()).collect();
+
+    let columns: Vec<String> = (0..w)
+        .map(|x| {
+            let cells: Vec<String> = (0..h)
+                .map(|y| {
+                    let (geo, features) = cell(grid[y][x]);
+                    format!("[[{geo}, [{features}]], [0, []], [0, []]]")
+                })
+                .collect();
+            format!("[{}]", cells.join(", "))
+        })
+        .collect();
+
+    let rest = TEMPLATE
+        .edit("#size: point(1, 1), #extraTiles: [0, 0, 0, 0]", &format!("#size: point({w}, {h}), #extraTiles: [1, 1, 1, 1]"))
+        .edit("#cameras: [], #selectedCamera: 0, #quads: []", "#cameras: [point(0, 0)], #selectedCamera: 0, #quads: [[[0, 0], [0, 0], [0, 0], [0, 0]]]");
+
+    format!("[{}]\n{rest}", columns.join(", "))
+}
+
+pub(crate) fn level(rows: &[&str]) -> RWLevel {
+    RWLevel::from_project_str("TEST", &project(rows)).expect("Bad fixture")
+}
+
+/// Text replacements in fixture projects, which fail the test rather than
+/// silently doing nothing when the project doesn't hold the text
+pub(crate) trait Edit {
+    fn edit(&self, from: &str, to: &str) -> String;
+}
+
+impl Edit for str {
+    fn edit(&self, from: &str, to: &str) -> String {
+        assert!(self.contains(from), "Fixture project has no {from:?}");
+        self.replace(from, to)
+    }
+}
@@ -0,0 +1,231 @@
+use std::array;
+
+use serde_json::Value;
+
+use crate::tile::{Feature, FeatureSet, Geometry, Tile, TileLayer};
+
+use super::TilePos;
+
+/// The tiles of every layer, from the foreground
+pub(super) type Layers = [TileLayer; 3];
+
+struct Builder {
+    layers: Layers,
+    dimensions: (usize, usize),
+    /// How many in-bounds tiles have been read, to catch missing ones
+    filled: usize,
+}
+
+impl Builder {
+    /// None if the dimensions need more tiles than the `available` ones,
+    /// so a bad size can't allocate more than the line could fill
+    fn new((w, h): (usize, usize), available: usize) -> Option<Self> {
+        if w.checked_mul(h)?.checked_mul(3)? > available {
+            return None
+        }
+
+        Some(Self {
+            layers: array::from_fn(|_| TileLayer::new(w, h)),
+            dimensions: (w, h),
+            filled: 0,
+        })
+    }
+
+    /// Store a tile, ignoring any outside the level's dimensions.
+    /// Fails on unknown geometry
+    fn set(&mut self, (layer, (x, y)): TilePos, geometry: u64, features: TileFeatures) -> Option<()> {
+        let (w, h) = self.dimensions;
+        if layer >= 3 || x >= w || y >= h {
+            return Some(())
+        }
+
+        let geometry = u8::try_from(geometry).ok().and_then(Geometry::from_data)?;
+        let layer = &mut self.layers[layer];
+        *layer.get_mut(x, y)? = Tile { geometry, features: features.set };
+        if let Some(listed) = features.listed {
+            layer.set_listed((x, y), listed);
+        }
+        self.filled += 1;
+
+        Some(())
+    }
+
+    fn finish(self) -> Option<Layers> {
+        let (w, h) = self.dimensions;
+        (self.filled == w * h * 3).then_some(self.layers)
+    }
+}
+
+/// The features of a tile as they're read
+#[derive(Default)]
+struct TileFeatures {
+    set: FeatureSet,
+    /// Every feature in the order read, kept only once they stop being in code
+    /// order or repeat, as most tiles list them in code order
+    listed: Option<Vec<Feature>>,
+}
+
+impl TileFeatures {
+    /// Add a feature by its code. Codes the editor doesn't know about are ignored
+    fn push(&mut self, code: u64) {
+        let Some(feature) = u8::try_from(code).ok().and_then(Feature::from_data) else {
+            return
+        };
+
+        // In code order so far, so the set lists the features read before this one
+        let in_order = self.set.bits() >> feature as u8 == 0;
+        if self.listed.is_none() && !in_order {
+            self.listed = Some(self.set.iter().collect());
+        }
+        if let Some(listed) = &mut self.listed {
+            listed.push(feature);
+        }
+        self.set.insert(feature);
+    }
+}
+
+/// Read the geometry line of a project, `[x][y][layer] = [geometry, [features]]`,
+/// straight from its text
+pub(super) fn parse(line: &str, dimensions: (usize, usize)) -> Option<Layers> {
+    // The shortest a tile can be written is `[0,[]]`
+    let mut geometry = Builder::new(dimensions, line.len() / 6)?;
+    let mut cursor = Cursor { bytes: line.as_bytes(), pos: 0 };
+
+    cursor.list(|cursor, x| cursor.list(|cursor, y| cursor.list(|cursor, layer| {
+        cursor.expect(b'[')?;
+        let geo = cursor.number()?;
+        cursor.expect(b',')?;
+
+        let mut features = TileFeatures::default();
+        cursor.list(|cursor, _| {
+            features.push(cursor.number()?);
+            Some(())
+        })?;
+        cursor.expect(b']')?;
+
+        geometry.set((layer, (x, y)), geo, features)
+    })))?;
+
+    cursor.skip_whitespace();
+    if cursor.pos != cursor.bytes.len() {
+        return None
+    }
+
+    geometry.finish()
+}
+
+/// Read the geometry line after it's been converted to JSON
+pub(super) fn from_json(line: &Value, dimensions: (usize, usize)) -> Option<Layers> {
+    let columns = line.as_array()?;
+    let available = columns.iter()
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(Value::as_array)
+        .map(Vec::len)
+        .sum();
+    let mut geometry = Builder::new(dimensions, available)?;
+
+    for (x, column) in columns.iter().enumerate() {
+        for (y, cell) in column.as_array()?.iter().enumerate() {
+            for (layer, tile) in cell.as_array()?.iter().enumerate() {
+                let geo = tile.get(0)?.as_u64()?;
+                let mut features = TileFeatures::default();
+                for code in tile.get(1)?.as_array()?.iter().filter_map(Value::as_u64) {
+                    features.push(code);
+                }
+
+                geometry.set((layer, (x, y)), geo, features)?;
+            }
+        }
+    }
+
+    geometry.finish()
+}
+
+/// Position in the bytes of a line of nested lists of integers
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume `byte` if it's next
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.bytes.get(self.pos) == Some(&byte);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.eat(byte).then_some(())
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut value: u64 = 0;
+        while let Some(digit) = self.bytes.get(self.pos).filter(|b| b.is_ascii_digit()) {
+            value = value.checked_mul(10)?.checked_add((digit - b'0') as u64)?;
+            self.pos += 1;
+        }
+
+        (self.pos > start).then_some(value)
+    }
+
+    /// Read a `[item, item, ...]` list, calling `item` with the index of each
+    fn list(&mut self, mut item: impl FnMut(&mut Self, usize) -> Option<()>) -> Option<()> {
+        self.expect(b'[')?;
+        if self.eat(b']') {
+            return Some(())
+        }
+
+        for idx in 0.. {
+            item(self, idx)?;
+            if self.eat(b']') {
+                break
+            }
+            self.expect(b',')?;
+        }
+
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rwlevel::{fixture, lingo_to_json::str_to_struct};
+
+    use super::{from_json, parse};
+
+    #[test]
+    fn test_parse_matches_json() {
+        let project = fixture::project(&[
+            "#E##",
+            "#o|#",
+            "#>.w",
+        ]);
+        let line = project.lines().next().unwrap();
+        let json = str_to_struct(&project).unwrap();
+
+        let streamed = parse(line, (4, 3)).unwrap();
+        let converted = from_json(&json._geom, (4, 3)).unwrap();
+        assert_eq!(streamed, converted);
+
+        // Features out of code order or repeated are kept as listed
+        let reordered = line.replacen("[0, [2]]", "[0, [2,1, 2 ]]", 1);
+        let layers = parse(&reordered, (4, 3)).unwrap();
+        assert_eq!(layers[0].duplicated().count(), 1);
+        assert_eq!(layers[0].tile_json(2, 1).unwrap().to_string(), "[0,[2,1,2]]");
+
+        assert!(parse(line, (5, 3)).is_none());
+        assert!(parse(&line[..line.len() - 1], (4, 3)).is_none());
+        assert!(parse(&line.replacen("[1, []]", "[8, []]", 1), (4, 3)).is_none());
+    }
+}
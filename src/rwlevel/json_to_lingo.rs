@@ -0,0 +1,112 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use super::lingo_to_json::ProjectJson;
+
+static DATA_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^(color|point|rect)\([0-9., \-]+\)$"#)
+        .expect("Failed to compile data type regex")
+});
+
+/// Inverse of `convert_lines`, producing the native project format.
+/// Lines are separated with `\r`, as the level editor does.
+pub(crate) fn write_lines(json: &ProjectJson) -> String {
+    let lines = [
+        &json._geom, &json._tiles, &json._effects,
+        &json._lights, &json._settings1, &json._settings2,
+        &json._cams, &json._water, &json._props,
+    ];
+
+    lines.map(to_lingo).join("\r")
+}
+
+/// Convert a single JSON value back into Lingo.
+/// Objects become property lists, and strings which were quoted
+/// by `fix_color_point` are unquoted again.
+pub(crate) fn to_lingo(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("VOID"),
+        Value::Bool(b) => out.push_str(if *b { "1" } else { "0" }),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => out.push_str(&i.to_string()),
+            // Lingo always prints floats with 4 decimal places
+            None => out.push_str(&format!("{:.4}", n.as_f64().unwrap_or_default())),
+        },
+        Value::String(s) if DATA_NAME.is_match(s) => out.push_str(s),
+        Value::String(s) => {
+            out.push('"');
+            out.push_str(s);
+            out.push('"');
+        }
+        Value::Array(arr) => {
+            out.push('[');
+            for (idx, item) in arr.iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(", ");
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => write_object(out, map),
+    }
+}
+
+fn write_object(out: &mut String, map: &Map<String, Value>) {
+    // An empty property list is written differently from an empty list
+    if map.is_empty() {
+        out.push_str("[:]");
+        return
+    }
+
+    out.push('[');
+    for (idx, (key, item)) in map.iter().enumerate() {
+        if idx > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(key);
+        out.push_str(": ");
+        write_value(out, item);
+    }
+    out.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rwlevel::{fixture::{self, Edit}, lingo_to_json::str_to_struct}, RWLevel};
+
+    use super::write_lines;
+
+    #[test]
+    fn test_round_trip() {
+        let project = fixture::project(&[
+            "#E##",
+            "#o|#",
+            "#>.w",
+        ]);
+
+        let json = str_to_struct(&project).unwrap();
+        let written = write_lines(&json);
+        assert_eq!(written, project.replace('\n', "\r"));
+
+        let reread = str_to_struct(&written).unwrap();
+        assert_eq!(json._geom, reread._geom);
+        assert_eq!(json._settings2, reread._settings2);
+    }
+
+    #[test]
+    fn test_effect_round_trip() {
+        let effect = r#"[#nm: "Slime", #tp: "standardErosion", #crossScreen: 0, #mtrx: [[0], [50], [12.5000]], #Options: [["Seed", [], 4]], #repeats: 130, #affectOpenAreas: 0.5000, #fill: 70]"#;
+        let project = fixture::project(&["#.#"]).edit("#effects: []", &format!("#effects: [{effect}]"));
+
+        let level = RWLevel::from_project_str("TEST", &project).unwrap();
+        assert_eq!(level.to_project_string(), project.replace('\n', "\r"));
+    }
+}
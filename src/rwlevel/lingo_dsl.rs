@@ -1,24 +1,30 @@
 use std::str::FromStr;
 
-#[derive(Debug)]
-pub(crate) struct Point {
-    pub(crate) fst: isize,
-    pub(crate) snd: isize,
+/// Lingo `point(x, y)`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point {
+    pub fst: isize,
+    pub snd: isize,
 }
 
-#[derive(Debug)]
-pub(crate) struct Color {
-    pub(crate) red: u8,
-    pub(crate) green: u8,
-    pub(crate) blue: u8,
+/// Lingo `color(r, g, b)`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
 }
 
-#[derive(Debug)]
-pub(crate) struct Rect {
-    pub(crate) top: isize,
-    pub(crate) left: isize,
-    pub(crate) bottom: isize,
-    pub(crate) right: isize,
+/// Lingo `rect(top, left, bottom, right)`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub top: isize,
+    pub left: isize,
+    pub bottom: isize,
+    pub right: isize,
 }
 
 fn collect<T: FromStr>(prefix: &str, s: &str, expected_len: usize) -> Option<Vec<T>> {
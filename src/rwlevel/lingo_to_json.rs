@@ -1,10 +1,13 @@
-use std::{borrow::Cow, fs::read_to_string, path::Path};
+use std::borrow::Cow;
 
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde_json::{value::Index, Value};
+use serde_json::Value;
 
-#[derive(Debug)]
+use super::sections::Line;
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct ProjectJson {
     pub(crate) _geom: Value,
     pub(crate) _tiles: Value,
@@ -17,44 +20,93 @@ pub(crate) struct ProjectJson {
     pub(crate) _props: Value,
 }
 
-pub(super) fn read_to_struct<P: AsRef<Path>>(file: P) -> Option<ProjectJson> {
-    let contents = read_to_string(&file).ok()?;
-    convert_lines(&contents, '\r')
-}
+/// Every line but the geometry of a freshly created project, holding
+/// the editor state which isn't part of a level's content.
+/// Public only so that the benchmarks can build projects from it
+#[doc(hidden)]
+pub const TEMPLATE: &str = r##"[#lastKeys: [], #Keys: [], #workLayer: 1, #lstMsPs: point(0, 0), #tlMatrix: [], #defaultMaterial: "Concrete", #toolType: "material", #toolData: "Big Metal", #tmPos: point(1, 1), #tmSavPosL: [], #specialEdit: 0]
+[#lastKeys: [], #Keys: [], #lstMsPs: point(0, 0), #effects: [], #emPos: point(1, 1), #editEffect: 0, #selectEditEffect: 0, #mode: "createNew", #brushSize: 5]
+[#pos: point(0, 0), #rot: 0, #sz: point(50, 70), #col: 1, #Keys: [], #lastKeys: [], #lastTm: 0, #lightAngle: 180, #flatness: 1, #lightRect: rect(1000, 1000, -1000, -1000), #paintShape: "pxl"]
+[#timeLimit: 4800, #defaultTerrain: 1, #maxFlies: 10, #flySpawnRate: 50, #lizards: [], #ambientSounds: [], #music: "NONE", #tags: [], #lightType: "Static", #waterDrips: 1, #lightRect: rect(0, 0, 1040, 800), #Matrix: []]
+[#mouse: 1, #lastMouse: 0, #mouseClick: 0, #pal: 1, #pals: [[#detCol: color( 255, 0, 0 )]], #eCol1: 1, #eCol2: 2, #totEcols: 5, #tileSeed: 0, #colGlows: [0, 0], #size: point(1, 1), #extraTiles: [0, 0, 0, 0], #light: 1]
+[#cameras: [], #selectedCamera: 0, #quads: [], #Keys: [], #lastKeys: []]
+[#waterLevel: -1, #waterInFront: 1, #waveLength: 60, #waveAmplitude: 5, #waveSpeed: 10]
+[#props: [], #lastKeys: [], #Keys: [], #workLayer: 1, #lstMsPs: point(0, 0), #pmPos: point(1, 1), #pmSavPosL: [], #propRotation: 0, #propStretchX: 1, #propStretchY: 1, #propFlipX: 1, #propFlipY: 1, #depth: 0, #color: 0]"##;
+
+impl ProjectJson {
+    /// A new project with the editor's default state and no geometry
+    pub(crate) fn template() -> Self {
+        convert_lines(&format!("[]\n{TEMPLATE}")).expect("Bad project template")
+    }
 
-fn convert_lines(contents: &str, newline: char) -> Option<ProjectJson> {
-
-    let maps: [Value; 9] = {
-        let mut iter = contents.split(newline)
-            .map(convert_to_json)
-            .filter_map(|line| serde_json::from_str(&line).ok());
-        
-        let mut maps: [Option<Value>; 9] = std::array::from_fn(|_| iter.next());
-        if maps.iter().any(Option::is_none) {
-            eprintln!("Invalid level editor project file!");
-            return None;
+    /// Every line null
+    pub(super) fn empty() -> Self {
+        Self {
+            _geom: Value::Null,
+            _tiles: Value::Null,
+            _effects: Value::Null,
+            _lights: Value::Null,
+            _settings1: Value::Null,
+            _settings2: Value::Null,
+            _cams: Value::Null,
+            _water: Value::Null,
+            _props: Value::Null,
         }
+    }
+
+    pub(crate) fn line_mut(&mut self, line: Line) -> &mut Value {
+        match line {
+            Line::Geometry => &mut self._geom,
+            Line::Tiles => &mut self._tiles,
+            Line::Effects => &mut self._effects,
+            Line::Lights => &mut self._lights,
+            Line::Settings1 => &mut self._settings1,
+            Line::Settings2 => &mut self._settings2,
+            Line::Cameras => &mut self._cams,
+            Line::Water => &mut self._water,
+            Line::Props => &mut self._props,
+        }
+    }
+}
+
+/// The level editor separates lines with `\r`, but files that have been
+/// through other tools (or git with autocrlf) may use `\n` instead.
+fn newline(contents: &str) -> char {
+    if contents.contains('\r') { '\r' } else { '\n' }
+}
+
+#[cfg(test)]
+pub(super) fn str_to_struct(contents: &str) -> Option<ProjectJson> {
+    convert_lines(contents)
+}
+
+/// The text of the nine lines of a project file, ignoring blank ones
+pub(super) fn split_lines(contents: &str) -> Option<[&str; 9]> {
+    let mut iter = contents.split(newline(contents))
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
 
-        std::array::from_fn(|i| maps[i].take().unwrap())
-    };
-
-    let [ 
-        _geom, _tiles, _effects, 
-        _lights, _settings1, _settings2, 
-        _cams, _water, _props 
-    ] = maps;
-
-    Some(ProjectJson {
-        _geom,
-        _tiles,
-        _effects,
-        _lights,
-        _settings1,
-        _settings2,
-        _cams,
-        _water,
-        _props,
-    })
+    let lines: [Option<&str>; 9] = std::array::from_fn(|_| iter.next());
+    if lines.iter().any(Option::is_none) {
+        eprintln!("Invalid level editor project file!");
+        return None;
+    }
+
+    Some(lines.map(Option::unwrap))
+}
+
+/// Convert one line of a project file
+pub(super) fn parse_line(line: &str) -> Option<Value> {
+    serde_json::from_str(&convert_to_json(line)?).ok()
+}
+
+fn convert_lines(contents: &str) -> Option<ProjectJson> {
+    let mut json = ProjectJson::empty();
+    for (line, text) in Line::ALL.into_iter().zip(split_lines(contents)?) {
+        *json.line_mut(line) = parse_line(text)?;
+    }
+
+    Some(json)
 }
 
 static INITIAL_REPLACEMENT: Lazy<Regex> = Lazy::new(|| {
@@ -63,7 +115,7 @@ static INITIAL_REPLACEMENT: Lazy<Regex> = Lazy::new(|| {
 });
 
 static DATA_NAME_REPLACEMENT: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r#"((color|point|rect)\([0-9, \-]+\))"#)
+    Regex::new(r#"((color|point|rect)\([0-9., \-]+\))"#)
         .expect("Failed to compile data type regex")
 });
 
@@ -80,27 +132,29 @@ static DATA_NAME_REPLACEMENT: Lazy<Regex> = Lazy::new(|| {
 /// matching `}` to terminate the newly-formatted JSON object.
 /// Quoting of #keys is done in later JSON conversion steps, and can be ignored
 /// here. Nested objects are handled via the linear outer iteration of the chars.
+///
+/// Only ASCII brackets are replaced, so this works on the bytes of the input.
 fn jsonify_lingo_objects(input: &str) -> String {
-    let mut chars: Vec<char> = input.chars().collect();
+    let mut chars: Vec<u8> = input.as_bytes().to_vec();
     'outer: for idx in 0..chars.len() {
         let c = chars[idx];
 
-        if c == '[' {
+        if c == b'[' {
             let mut idx2 = idx;
 
             //Handle nesting: keep traversing `[`s until we find the start
             //of actual data.
-            while chars[idx2] == '[' {
+            while chars.get(idx2) == Some(&b'[') {
                 idx2 += 1;
             }
 
             //Not a nested object, just an array
-            if chars[idx2] != '#' {
+            if chars.get(idx2) != Some(&b'#') {
                 continue 'outer;
             }
 
             //Replace the `[`.
-            chars[idx2 - 1] = '{';
+            chars[idx2 - 1] = b'{';
 
             //Depth tracks how many `[` and `]`s have been encountered.
             //Need to iterate through the chars from idx2 to the end until depth
@@ -116,15 +170,15 @@ fn jsonify_lingo_objects(input: &str) -> String {
                     //another nested structure. Processing of nested
                     //objects is not needed, as it'll be fixed in a later
                     //iteration of the 'outer loop.
-                    '[' => depth += 1,
+                    b'[' => depth += 1,
                     //Decrement the depth.
                     //If depth is now 0, then this `]` is the matching
                     //bracket for the one starting the object we're
                     //reformatting.
-                    ']' => {
+                    b']' => {
                         depth -= 1;
                         if depth == 0 {
-                            chars[idx2] = '}';
+                            chars[idx2] = b'}';
                             continue 'outer;
                         }
                     }
@@ -136,7 +190,7 @@ fn jsonify_lingo_objects(input: &str) -> String {
         }
     }
 
-    chars.into_iter().collect()
+    String::from_utf8(chars).expect("Replaced a byte which wasn't ASCII")
 }
 
 /// Quote the keys in the input
@@ -149,66 +203,38 @@ fn fix_color_point(input: &str) -> Cow<'_, str> {
     DATA_NAME_REPLACEMENT.replace_all(input, "\"$1\"")
 }
 
-/// Replace the surrounding `[` and `]` with `{` and `}`
-fn wrap_in_braces(input: &str) -> String {
-    let mut chars: Vec<char> = input.chars().collect();
-    
-    if let Some(first) = chars.first_mut() {
-        *first = '{';
-    } else {
-        panic!("Bad input - Couldn't replace the '[' at the start with '{{'");
-    }
-    
-    if let Some(last) = chars.last_mut() {
-        *last = '}';
-    } else {
-        panic!("Bad input - Couldn't replace the ']' at the end with '}}'");
-    }
-
-    chars.into_iter().collect()
+/// Replace the surrounding brackets with `{` and `}`, or None if the
+/// input doesn't start and end with brackets
+fn wrap_in_braces(input: &str) -> Option<String> {
+    let inner = input.strip_prefix(['[', '{'])?.strip_suffix([']', '}'])?;
+    Some(format!("{{{inner}}}"))
 }
 
-/// Massage the rain world native project format into JSON
-fn convert_to_json(input: &str) -> Cow<'_, str> {
+/// Massage the rain world native project format into JSON, or None if
+/// the line isn't bracketed
+fn convert_to_json(input: &str) -> Option<Cow<'_, str>> {
     //Don't need to process any further. This line has no JSON-like structures to fix
     if !input.contains("#") {
-        return Cow::Borrowed(input);
+        return Some(Cow::Borrowed(input));
     }
 
     let work = jsonify_lingo_objects(input);
     let work = rename_keys(&work);
     let work = fix_color_point(&work);
 
-    return Cow::Owned(wrap_in_braces(&work))
-}
-
-pub(crate) trait BetterIndexing {
-    type Output;
-
-    fn index(&self, path: &[&dyn Index]) -> Option<&Self::Output>;
-}
-
-impl BetterIndexing for Value {
-    type Output = Value;
-    fn index(&self, path: &[&dyn Index]) -> Option<&Self::Output> {
-        if path.is_empty() {
-            return Some(self)
-        }
-
-        self.get(path[0])?.index(&path[1..])
-    }
+    wrap_in_braces(&work).map(Cow::Owned)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::rwlevel::lingo_to_json::{convert_to_json, BetterIndexing};
+    use crate::rwlevel::lingo_to_json::convert_to_json;
 
     use super::convert_lines;
 
     #[test]
     fn test_json_convert() {
         let line = "[#cameras: [point(20, 30)], #selectedCamera: 0, #quads: [[[0, 0], [0, 0], [0, 0], [0, 0]]], #Keys: [#n: 0, #d: 0, #e: 0, #p: 0], #lastKeys: [#n: 0, #d: 0, #e: 0, #p: 0]]";
-        let fixed = convert_to_json(line);
+        let fixed = convert_to_json(line).unwrap();
 
         println!("{line}");
         println!("{fixed}");
@@ -226,7 +252,7 @@ mod tests {
 [#waterLevel: -1, #waterInFront: 1, #waveLength: 60, #waveAmplitude: 5, #waveSpeed: 10]
 [#props: [], #lastKeys: [], #Keys: [], #workLayer: 1, #lstMsPs: point(0, 0), #pmPos: point(1, 1), #pmSavPosL: [], #propRotation: 0, #propStretchX: 1, #propStretchY: 1, #propFlipX: 1, #propFlipY: 1, #depth: 0, #color: 0]"##;
 
-        let json = convert_lines(lines, '\n').unwrap();
+        let json = convert_lines(lines).unwrap();
         // let _size: Point = dbg!(
         //     dbg!(json._settings2.get("#size"))
         //         .and_then(Value::as_str)
@@ -241,6 +267,6 @@ mod tests {
         // let _tiles = load_tiles(&json, &meta);
         // dbg!(&_tiles[0]);
 
-        dbg!(json._geom.index(&[&0, &1, &1, &1]).unwrap());
+        dbg!(&json._geom[0][1][1][1]);
     }
 }
\ No newline at end of file
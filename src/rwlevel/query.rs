@@ -0,0 +1,171 @@
+//! Walking the tiles of a level by position, so analyses don't each redo the
+//! index math, and finding the connected open areas of a layer.
+
+use crate::{grid::{Connectivity, Regions}, Feature, Geometry, Tile, TileLayer};
+
+use super::RWLevel;
+
+impl RWLevel {
+    /// Every tile of the layer as `(x, y, tile)`, row by row from the top.
+    /// Empty if there's no such layer
+    pub fn tiles(&self, layer: usize) -> impl Iterator<Item = (usize, usize, Tile)> + '_ {
+        self.layer(layer).into_iter().flat_map(TileLayer::iter)
+    }
+
+    /// Tiles with the feature on any layer, as `(layer, x, y, tile)`
+    pub fn tiles_with_feature(&self, feature: Feature) -> impl Iterator<Item = (usize, usize, usize, Tile)> + '_ {
+        self.all_tiles().filter(move |(_, _, _, tile)| tile.features.contains(&feature))
+    }
+
+    /// Tiles with the geometry on any layer, as `(layer, x, y, tile)`
+    pub fn tiles_with_geometry(&self, geometry: Geometry) -> impl Iterator<Item = (usize, usize, usize, Tile)> + '_ {
+        self.all_tiles().filter(move |(_, _, _, tile)| tile.geometry == geometry)
+    }
+
+    /// The tiles next to (x, y) on the layer as `(x, y, tile)`, clockwise from above.
+    /// Positions outside the level are skipped
+    pub fn neighbours(&self, layer: usize, x: usize, y: usize, connectivity: Connectivity) -> impl Iterator<Item = (usize, usize, Tile)> + '_ {
+        self.layer(layer)
+            .into_iter()
+            .flat_map(move |tiles| tiles.geometry()
+                .neighbours(x, y, connectivity)
+                .filter_map(move |(nx, ny)| Some((nx, ny, tiles.get(nx, ny)?))))
+    }
+
+    /// The layer nearest the front which is solid at (x, y), or None if every
+    /// layer is open there or it's outside the level
+    pub fn first_solid_layer(&self, x: usize, y: usize) -> Option<usize> {
+        (0..3).find(|&layer| self.tile(layer, x, y).is_some_and(|tile| tile.geometry.is_solid()))
+    }
+
+    /// The connected areas of tiles which aren't solid on the layer.
+    /// Empty if there's no such layer
+    pub fn open_regions(&self, layer: usize, connectivity: Connectivity) -> Regions {
+        self.layer(layer).map_or_else(Regions::default, |tiles| {
+            tiles.geometry().regions(connectivity, |geometry| !geometry.is_solid())
+        })
+    }
+
+    /// Bucket fill: set the geometry of every tile connected to (x, y) which has
+    /// the same geometry as it, keeping their features. Returns how many
+    /// tiles changed, 0 if (x, y) is outside the level
+    pub fn fill_geometry(&mut self, layer: usize, x: usize, y: usize, geometry: Geometry, connectivity: Connectivity) -> usize {
+        let Some(target) = self.tile(layer, x, y).map(|tile| tile.geometry).filter(|&g| g != geometry) else {
+            return 0
+        };
+
+        let filled = self.layer(layer)
+            .expect("Tile is on this layer")
+            .geometry()
+            .flood_fill(x, y, connectivity, |&g| g == target);
+        for &(fx, fy) in &filled {
+            self.tile_mut(layer, fx, fy).expect("Filled tile is in the level").geometry = geometry;
+        }
+
+        filled.len()
+    }
+
+    fn all_tiles(&self) -> impl Iterator<Item = (usize, usize, usize, Tile)> + '_ {
+        (0..3).flat_map(move |layer| self.tiles(layer).map(move |(x, y, tile)| (layer, x, y, tile)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rwlevel::fixture, Connectivity, Feature, Geometry, RWLevel};
+
+    #[test]
+    fn test_queries() {
+        let level = fixture::level(&["#.w", "G.w"]);
+
+        let tiles: Vec<_> = level.tiles(RWLevel::L1_FG).map(|(x, y, tile)| (x, y, tile.geometry)).collect();
+        assert_eq!(tiles[..3], [(0, 0, Geometry::Wall), (1, 0, Geometry::Air), (2, 0, Geometry::Air)]);
+        assert_eq!(level.tiles(3).count(), 0);
+
+        let wormgrass: Vec<_> = level.tiles_with_feature(Feature::WormGrass).map(|(l, x, y, _)| (l, x, y)).collect();
+        assert_eq!(wormgrass, [(0, 2, 0), (0, 2, 1)]);
+        assert_eq!(level.tiles_with_geometry(Geometry::Glass).map(|(l, x, y, _)| (l, x, y)).collect::<Vec<_>>(), [(0, 0, 1)]);
+
+        let around: Vec<_> = level.neighbours(RWLevel::L1_FG, 1, 0, Connectivity::Four).map(|(x, y, _)| (x, y)).collect();
+        assert_eq!(around, [(2, 0), (1, 1), (0, 0)]);
+        assert_eq!(level.neighbours(RWLevel::L1_FG, 1, 0, Connectivity::Eight).count(), 5);
+
+        assert_eq!(level.first_solid_layer(0, 1), Some(RWLevel::L1_FG));
+        assert_eq!(level.first_solid_layer(1, 0), None);
+        assert_eq!(level.first_solid_layer(5, 5), None);
+    }
+
+    #[test]
+    fn test_query_layers() {
+        let mut level = fixture::level(&["#.", ".w"]);
+        level.tile_mut(RWLevel::L2_MG, 1, 0).unwrap().geometry = Geometry::Wall;
+        level.tile_mut(RWLevel::L3_BG, 0, 1).unwrap().geometry = Geometry::Glass;
+        level.tile_mut(RWLevel::L3_BG, 1, 1).unwrap().add_features(&[Feature::WormGrass]);
+
+        // Features and geometry are found on every layer, front first
+        let wormgrass: Vec<_> = level.tiles_with_feature(Feature::WormGrass).map(|(l, x, y, _)| (l, x, y)).collect();
+        assert_eq!(wormgrass, [(RWLevel::L1_FG, 1, 1), (RWLevel::L3_BG, 1, 1)]);
+        let solid: Vec<_> = level.tiles_with_geometry(Geometry::Wall).map(|(l, x, y, _)| (l, x, y)).collect();
+        assert_eq!(solid, [(RWLevel::L1_FG, 0, 0), (RWLevel::L2_MG, 1, 0)]);
+
+        assert_eq!(level.first_solid_layer(0, 0), Some(RWLevel::L1_FG));
+        assert_eq!(level.first_solid_layer(1, 0), Some(RWLevel::L2_MG));
+        assert_eq!(level.first_solid_layer(0, 1), Some(RWLevel::L3_BG));
+        assert_eq!(level.first_solid_layer(1, 1), None);
+
+        // Corners only have the neighbours inside the level
+        let corner = |x, y, connectivity| level.neighbours(RWLevel::L2_MG, x, y, connectivity).map(|(x, y, _)| (x, y)).collect::<Vec<_>>();
+        assert_eq!(corner(0, 0, Connectivity::Four), [(1, 0), (0, 1)]);
+        assert_eq!(corner(1, 1, Connectivity::Eight), [(1, 0), (0, 1), (0, 0)]);
+        assert_eq!(level.neighbours(RWLevel::L2_MG, 1, 0, Connectivity::Four).next().unwrap().2.geometry, Geometry::Air);
+
+        // No such layer, or outside the level
+        assert_eq!(level.neighbours(3, 0, 0, Connectivity::Eight).count(), 0);
+        assert_eq!(level.neighbours(RWLevel::L1_FG, 5, 5, Connectivity::Four).count(), 0);
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut level = fixture::level(&[
+            "#..#.",
+            "####.",
+            "#.>#.",
+        ]);
+
+        let regions = level.open_regions(RWLevel::L1_FG, Connectivity::Four);
+        let sizes: Vec<_> = regions.regions.iter().map(|r| (r.start, r.size)).collect();
+        assert_eq!(sizes, [((1, 0), 2), ((4, 0), 3), ((1, 2), 2)]);
+
+        assert_eq!(level.fill_geometry(RWLevel::L1_FG, 4, 1, Geometry::Wall, Connectivity::Four), 3);
+        assert_eq!(level.fill_geometry(RWLevel::L1_FG, 4, 1, Geometry::Wall, Connectivity::Four), 0);
+        assert_eq!(level.open_regions(RWLevel::L1_FG, Connectivity::Four).regions.len(), 2);
+    }
+
+    #[test]
+    fn test_fill_border() {
+        let mut level = fixture::level(&[
+            ".#..",
+            "#..#",
+            "..#.",
+        ]);
+
+        // Regions running along the edges and into the corners keep their bounds inside the level
+        let regions = level.open_regions(RWLevel::L1_FG, Connectivity::Four);
+        let found: Vec<_> = regions.regions.iter().map(|r| (r.start, r.size, r.bounds)).collect();
+        assert_eq!(found, [((0, 0), 1, (0, 0, 0, 0)), ((2, 0), 6, (0, 0, 3, 2)), ((3, 2), 1, (3, 2, 3, 2))]);
+        assert_eq!(regions.at(3, 0), regions.at(0, 2));
+        assert!(regions.at(4, 0).is_none());
+
+        // Diagonal steps join the corners to the middle
+        let regions = level.open_regions(RWLevel::L1_FG, Connectivity::Eight);
+        assert_eq!(regions.regions.len(), 1);
+        assert_eq!(regions.regions[0].bounds, (0, 0, 3, 2));
+
+        // Filling from a corner stops at the edges, and outside the level fills nothing
+        assert_eq!(level.fill_geometry(RWLevel::L1_FG, 3, 2, Geometry::Glass, Connectivity::Eight), 8);
+        assert_eq!(level.tile(RWLevel::L1_FG, 0, 0).unwrap().geometry, Geometry::Glass);
+        assert_eq!(level.fill_geometry(RWLevel::L1_FG, 4, 2, Geometry::Wall, Connectivity::Eight), 0);
+        assert_eq!(level.fill_geometry(RWLevel::L1_FG, 0, 3, Geometry::Wall, Connectivity::Eight), 0);
+        assert!(level.open_regions(3, Connectivity::Four).regions.is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Loading only the parts of a project file a tool needs. Sections which
+//! aren't asked for are kept as text, and parsed the first time they're used.
+
+use std::ops::BitOr;
+
+use serde_json::Value;
+
+use super::{deferred::Deferred, lingo_to_json::{parse_line, split_lines, ProjectJson}};
+
+/// A part of a project file, see [`RWLevel::from_project_str_sections`](super::RWLevel::from_project_str_sections)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Section {
+    /// Geometry and features of every tile
+    Geometry,
+    /// Tiles and materials placed in the tile editor
+    Tiles,
+    Effects,
+    Lights,
+    /// Level and render settings, including the dimensions
+    Settings,
+    Cameras,
+    Water,
+    Props,
+}
+
+/// A set of [`Section`]s
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Sections(u8);
+
+impl Sections {
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self(u8::MAX);
+
+    pub const fn with(self, section: Section) -> Self {
+        Self(self.0 | 1 << section as u8)
+    }
+
+    pub const fn contains(self, section: Section) -> bool {
+        self.0 & 1 << section as u8 != 0
+    }
+}
+
+impl From<Section> for Sections {
+    fn from(section: Section) -> Self {
+        Self::NONE.with(section)
+    }
+}
+
+impl<S: Into<Sections>> BitOr<S> for Sections {
+    type Output = Sections;
+
+    fn bitor(self, rhs: S) -> Sections {
+        Self(self.0 | rhs.into().0)
+    }
+}
+
+impl<S: Into<Sections>> BitOr<S> for Section {
+    type Output = Sections;
+
+    fn bitor(self, rhs: S) -> Sections {
+        Sections::from(self) | rhs
+    }
+}
+
+/// The lines of a project file, in order
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Line {
+    Geometry,
+    Tiles,
+    Effects,
+    Lights,
+    /// Level settings
+    Settings1,
+    /// Render settings
+    Settings2,
+    Cameras,
+    Water,
+    Props,
+}
+
+impl Line {
+    pub(crate) const ALL: [Line; 9] = [
+        Line::Geometry, Line::Tiles, Line::Effects,
+        Line::Lights, Line::Settings1, Line::Settings2,
+        Line::Cameras, Line::Water, Line::Props,
+    ];
+
+    pub(crate) fn section(self) -> Section {
+        match self {
+            Line::Geometry => Section::Geometry,
+            Line::Tiles => Section::Tiles,
+            Line::Effects => Section::Effects,
+            Line::Lights => Section::Lights,
+            Line::Settings1 | Line::Settings2 => Section::Settings,
+            Line::Cameras => Section::Cameras,
+            Line::Water => Section::Water,
+            Line::Props => Section::Props,
+        }
+    }
+}
+
+/// The lines of a project. The geometry is only kept as tiles, so its line is null
+#[derive(Clone, Debug)]
+pub(super) struct LazyProject([Deferred<Value>; 9]);
+
+impl LazyProject {
+    pub(super) fn parsed(json: ProjectJson) -> Self {
+        let mut json = json;
+        Self(Line::ALL.map(|line| match line {
+            Line::Geometry => Deferred::parsed(Value::Null),
+            line => Deferred::parsed(json.line_mut(line).take()),
+        }))
+    }
+
+    /// The text of the geometry line, and the other lines with those in
+    /// `sections` parsed. None if the file or any of those lines is bad
+    pub(super) fn split(contents: &str, sections: Sections) -> Option<(&str, Self)> {
+        let lines = split_lines(contents)?;
+
+        let mut project = Vec::with_capacity(9);
+        for (line, text) in Line::ALL.into_iter().zip(lines) {
+            project.push(match line {
+                Line::Geometry => Deferred::parsed(Value::Null),
+                line if sections.contains(line.section()) => Deferred::parsed(parse_line(text)?),
+                _ => Deferred::raw(text),
+            });
+        }
+
+        let project = project.try_into().ok()?;
+        Some((lines[0], Self(project)))
+    }
+
+    /// The line, or None if its text is bad
+    pub(super) fn try_line(&self, line: Line) -> Option<&Value> {
+        self.0[line as usize].try_get(parse_line)
+    }
+
+    /// A value read from the line, now if it's parsed, otherwise on first use
+    pub(super) fn read<T>(&self, line: Line, read: impl FnOnce(&Value) -> T) -> Deferred<T> {
+        self.0[line as usize].map(read)
+    }
+
+    pub(super) fn line(&self, line: Line) -> &Value {
+        self.0[line as usize].get("project line", parse_line)
+    }
+
+    pub(super) fn line_mut(&mut self, line: Line) -> &mut Value {
+        self.0[line as usize].get_mut("project line", parse_line)
+    }
+
+    /// Every line parsed, with the geometry left null
+    pub(super) fn to_json(&self) -> ProjectJson {
+        let mut json = ProjectJson::empty();
+        for line in Line::ALL {
+            *json.line_mut(line) = self.line(line).clone();
+        }
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rwlevel::{fixture::{self, Edit}, RWLevel};
+
+    use super::{Section, Sections};
+
+    #[test]
+    fn test_sections() {
+        let project = fixture::project(&["#.|#"]);
+        let level = RWLevel::from_project_str("TEST", &project).unwrap();
+
+        // A broken line is only noticed if its section is asked for
+        let broken = project.edit("#effects: []", "#effects: [[#nm: \"Slime\"]]");
+        assert!(RWLevel::from_project_str("TEST", &broken).is_none());
+        assert!(RWLevel::from_project_str_sections("TEST", &broken, Section::Geometry | Section::Props).is_some());
+
+        let lazy = RWLevel::from_project_str_sections("TEST", &project, Sections::NONE).unwrap();
+        assert_eq!(lazy.meta().dimensions(), (4, 1));
+        assert_eq!(lazy.tile(RWLevel::L1_FG, 2, 0), level.tile(RWLevel::L1_FG, 2, 0));
+        assert_eq!(lazy.to_project_string(), level.to_project_string());
+
+        // Bad sections which weren't asked for are reported as they're used
+        let mut lines: Vec<&str> = broken.lines().collect();
+        lines[0] = "[[[1, [], [";
+        lines[7] = "[#waterLevel: 4";
+        let broken = lines.join("\n");
+        let lazy = RWLevel::from_project_str_sections("TEST", &broken, Section::Settings.into()).unwrap();
+        assert_eq!(lazy.tile(RWLevel::L1_FG, 2, 0), None);
+        assert!(lazy.try_effects().is_none());
+        assert_eq!(lazy.meta().water_level(), -1);
+        assert_eq!(lazy.meta().cameras().len(), 1);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,517 @@
+//! A documented JSON representation of a whole level, for tools written in
+//! other languages. Unlike [RWLevel::to_raw_json], editor UI state is left
+//! out and every grid is indexed the same way.
+//!
+//! # Schema, version 1
+//!
+//! ```text
+//! {
+//!   "format": "rainworld_level",
+//!   "version": 1,
+//!   "name": "SU_A01",
+//!   "width": 72, "height": 43,
+//!   "extraTiles": { "left": 12, "top": 3, "right": 12, "bottom": 5 },
+//!   "geometry": [ layer1, layer2, layer3 ],
+//!   "tiles": { "defaultMaterial": "Concrete", "layers": [ layer1, layer2, layer3 ] },
+//!   "effects": [ { "name", "matrix", "options", "extra" } ],
+//!   "props": [ { "depth", "name", "category", "index", "quad", "settings" } ],
+//!   "cameras": [ { "x", "y", "quad" } ],
+//!   "water": { "level", "inFront", "waveLength", "waveAmplitude", "waveSpeed" },
+//!   "light": { "angle", "flatness" },
+//!   "settings": { ... },
+//!   "renderSettings": { ... }
+//! }
+//! ```
+//!
+//! All grids are arrays of rows, top row first, so a cell is read as
+//! `grid[y][x]`. Layer 1 is the foreground.
+//!
+//! - `geometry` cells are `[geometry, [features...]]`, using the editor's codes
+//!   (see [Geometry] and [Feature]), e.g. `[7, [4]]` for a shortcut entrance.
+//! - `tiles` cells are `null` for no tile, or one of
+//!   `{"type": "material", "material": "Concrete"}`,
+//!   `{"type": "head", "category": 3, "index": 12, "name": "Big Pipe"}`,
+//!   `{"type": "body", "head": [x, y], "layer": 1}`. Head positions and
+//!   categories are 1-based, as in the editor. Unrecognised cells are kept as
+//!   `{"type": <editor type>, "data": <editor data>}`.
+//! - `effects` are in render order. `matrix` holds the strength of the effect
+//!   per tile from 0 to 100, `options` is the editor's list of
+//!   `[name, choices, value]`, and `extra` holds any other fields of the effect.
+//! - `props` are in render order. `quad` is the four corners of the prop as
+//!   `[x, y]` pairs in prop editor pixels, and `settings` holds the prop's settings.
+//! - `cameras` are positioned by their top-left corner in editor pixels (20 per tile),
+//!   and `quad` is the `[angle, radius]` offset of each corner.
+//! - `water.level` is counted in tiles from the bottom of the level, -1 for no water.
+//! - `settings` and `renderSettings` are the remaining level and render settings.
+//!
+//! Keys inside `extra`, `settings` and `renderSettings` objects, at any depth,
+//! are the editor's property names without the leading `#`. Lingo values such as
+//! `point(1, 2)`, `rect(0, 0, 10, 10)` and `color(255, 0, 0)` are kept as strings.
+
+use serde_json::{json, Map, Value};
+
+use crate::{rwlevel::{canonical::is_editor_state, lingo_to_json::ProjectJson}, Feature, Geometry, RWLevel};
+
+pub const FORMAT: &str = "rainworld_level";
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// Keys of the render settings line which are stored elsewhere in the schema
+const RENDER_SETTINGS_SKIPPED: &[&str] = &["#size", "#extraTiles"];
+
+impl RWLevel {
+    /// The level in the documented JSON schema. See the [schema](crate::schema) module
+    pub fn export_json(&self) -> Value {
+        let project = self.to_project();
+        let meta = self.meta();
+        let (w, h) = meta.dimensions();
+        let (left, top, right, bottom) = meta.extra_tiles();
+
+        let geometry: Vec<Value> = [RWLevel::L1_FG, RWLevel::L2_MG, RWLevel::L3_BG]
+            .into_iter()
+            .map(|layer| rows(w, h, |x, y| self.layer(layer).and_then(|tiles| tiles.tile_json(x, y)).expect("Tile out of bounds")))
+            .collect();
+
+        let tile_matrix = project._tiles.get("#tlMatrix");
+        let tiles: Vec<Value> = (0..3)
+            .map(|layer| rows(w, h, |x, y| {
+                tile_matrix
+                    .and_then(|m| m.get(x)?.get(y)?.get(layer))
+                    .map_or(Value::Null, export_tile_cell)
+            }))
+            .collect();
+
+        let effects: Vec<Value> = self.effects()
+            .iter()
+            .map(|effect| {
+                let extra: Map<String, Value> = effect.entry
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter(|(key, _)| !["#nm", "#mtrx", "#Options"].contains(&key.as_str()))
+                    .map(|(key, value)| (strip_key(key), strip_keys(value)))
+                    .collect();
+
+                json!({
+                    "name": effect.name(),
+                    "matrix": rows(w, h, |x, y| {
+                        effect.matrix().get(x, y).copied().unwrap_or_default().into()
+                    }),
+                    "options": effect.options(),
+                    "extra": extra,
+                })
+            })
+            .collect();
+
+        let props: Vec<Value> = project._props.get("#props")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(export_prop)
+            .collect();
+
+        let quads = project._cams.get("#quads").and_then(Value::as_array);
+        let cameras: Vec<Value> = meta.cameras()
+            .iter()
+            .enumerate()
+            .map(|(idx, cam)| json!({
+                "x": cam.fst,
+                "y": cam.snd,
+                "quad": quads.and_then(|q| q.get(idx)).cloned().unwrap_or_else(|| json!([[0, 0], [0, 0], [0, 0], [0, 0]])),
+            }))
+            .collect();
+
+        let water = |key: &str, default: i64| project._water.get(key).and_then(Value::as_i64).unwrap_or(default);
+
+        let render_settings: Map<String, Value> = project._settings2
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| !RENDER_SETTINGS_SKIPPED.contains(&key.as_str()) && !is_editor_state("render", key))
+            .map(|(key, value)| (strip_key(key), strip_keys(value)))
+            .collect();
+
+        json!({
+            "format": FORMAT,
+            "version": SCHEMA_VERSION,
+            "name": self.name(),
+            "width": w,
+            "height": h,
+            "extraTiles": { "left": left, "top": top, "right": right, "bottom": bottom },
+            "geometry": geometry,
+            "tiles": {
+                "defaultMaterial": project._tiles.get("#defaultMaterial").cloned().unwrap_or_else(|| "Concrete".into()),
+                "layers": tiles,
+            },
+            "effects": effects,
+            "props": props,
+            "cameras": cameras,
+            "water": {
+                "level": meta.water_level(),
+                "inFront": meta.water_in_front(),
+                "waveLength": water("#waveLength", 60),
+                "waveAmplitude": water("#waveAmplitude", 5),
+                "waveSpeed": water("#waveSpeed", 10),
+            },
+            "light": {
+                "angle": project._lights.get("#lightAngle").cloned().unwrap_or_else(|| 180.into()),
+                "flatness": project._lights.get("#flatness").cloned().unwrap_or_else(|| 1.into()),
+            },
+            "settings": strip_keys(&project._settings1),
+            "renderSettings": render_settings,
+        })
+    }
+
+    /// Build a level from the documented JSON schema. Editor UI state is reset
+    /// to what a new project would have
+    pub fn import_json(value: &Value) -> Result<Self, String> {
+        if value.get("format").and_then(Value::as_str) != Some(FORMAT) {
+            return Err(format!("not a {FORMAT} document"))
+        }
+        match value.get("version").and_then(Value::as_u64) {
+            Some(SCHEMA_VERSION) => {}
+            Some(version) => return Err(format!("unsupported schema version {version}")),
+            None => return Err("missing schema version".into()),
+        }
+
+        let name = value.get("name").and_then(Value::as_str).ok_or("missing name")?;
+        let w = field_usize(value, "width")?;
+        let h = field_usize(value, "height")?;
+        if w == 0 || h == 0 {
+            return Err("levels must be at least 1x1".into())
+        }
+
+        let mut project = ProjectJson::template();
+
+        // Geometry, stored by the editor as [x][y][layer]
+        let geometry = layers(value.get("geometry"), w, h, "geometry")?;
+        let mut geom = vec![vec![Vec::with_capacity(3); h]; w];
+        for (layer, grid) in geometry.iter().enumerate() {
+            for (y, row) in grid.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    geom[x][y].push(import_geometry_cell(cell)
+                        .ok_or_else(|| format!("bad geometry cell at ({x}, {y}) on layer {}", layer + 1))?);
+                }
+            }
+        }
+        project._geom = json!(geom);
+
+        let tiles = value.get("tiles").ok_or("missing tiles")?;
+        let tile_layers = layers(tiles.get("layers"), w, h, "tiles")?;
+        let mut tl_matrix = vec![vec![Vec::with_capacity(3); h]; w];
+        for (layer, grid) in tile_layers.iter().enumerate() {
+            for (y, row) in grid.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    tl_matrix[x][y].push(import_tile_cell(cell)
+                        .ok_or_else(|| format!("bad tile cell at ({x}, {y}) on layer {}", layer + 1))?);
+                }
+            }
+        }
+        project._tiles["#tlMatrix"] = json!(tl_matrix);
+        if let Some(material) = tiles.get("defaultMaterial") {
+            project._tiles["#defaultMaterial"] = material.clone();
+        }
+
+        let effects = value.get("effects").and_then(Value::as_array).ok_or("missing effects")?;
+        project._effects["#effects"] = effects.iter()
+            .enumerate()
+            .map(|(idx, effect)| import_effect(effect, w, h).ok_or_else(|| format!("bad effect {idx}")))
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+
+        let props = value.get("props").and_then(Value::as_array).ok_or("missing props")?;
+        project._props["#props"] = props.iter()
+            .enumerate()
+            .map(|(idx, prop)| import_prop(prop).ok_or_else(|| format!("bad prop {idx}")))
+            .collect::<Result<Vec<_>, _>>()?
+            .into();
+
+        let cameras = value.get("cameras").and_then(Value::as_array).ok_or("missing cameras")?;
+        let mut positions = vec![];
+        let mut quads = vec![];
+        for (idx, camera) in cameras.iter().enumerate() {
+            let x = camera.get("x").and_then(Value::as_i64).ok_or_else(|| format!("bad camera {idx}"))?;
+            let y = camera.get("y").and_then(Value::as_i64).ok_or_else(|| format!("bad camera {idx}"))?;
+            positions.push(Value::String(format!("point({x}, {y})")));
+            quads.push(camera.get("quad").cloned().unwrap_or_else(|| json!([[0, 0], [0, 0], [0, 0], [0, 0]])));
+        }
+        project._cams["#cameras"] = positions.into();
+        project._cams["#quads"] = quads.into();
+
+        if let Some(water) = value.get("water") {
+            let level = water.get("level").and_then(Value::as_i64).unwrap_or(-1);
+            let in_front = water.get("inFront").and_then(Value::as_bool).unwrap_or(true);
+            project._water["#waterLevel"] = level.into();
+            project._water["#waterInFront"] = (in_front as u8).into();
+            for (key, field) in [("#waveLength", "waveLength"), ("#waveAmplitude", "waveAmplitude"), ("#waveSpeed", "waveSpeed")] {
+                if let Some(v) = water.get(field) {
+                    project._water[key] = v.clone();
+                }
+            }
+        }
+
+        if let Some(light) = value.get("light") {
+            for (key, field) in [("#lightAngle", "angle"), ("#flatness", "flatness")] {
+                if let Some(v) = light.get(field) {
+                    project._lights[key] = v.clone();
+                }
+            }
+        }
+
+        if let Some(settings) = value.get("settings").and_then(Value::as_object) {
+            for (key, v) in settings {
+                project._settings1[format!("#{key}")] = add_keys(v);
+            }
+        }
+
+        if let Some(settings) = value.get("renderSettings").and_then(Value::as_object) {
+            for (key, v) in settings {
+                project._settings2[format!("#{key}")] = add_keys(v);
+            }
+        }
+
+        let extra = value.get("extraTiles");
+        let border = |side: &str| extra.and_then(|e| e.get(side)).and_then(Value::as_u64).unwrap_or(0);
+        project._settings2["#size"] = Value::String(format!("point({w}, {h})"));
+        project._settings2["#extraTiles"] = json!([border("left"), border("top"), border("right"), border("bottom")]);
+
+        RWLevel::from_project(name.to_owned(), project).ok_or_else(|| "bad settings, geometry or effects".into())
+    }
+}
+
+fn field_usize(value: &Value, key: &str) -> Result<usize, String> {
+    value.get(key)
+        .and_then(Value::as_u64)
+        .map(|v| v as usize)
+        .ok_or_else(|| format!("missing or bad {key}"))
+}
+
+/// A w by h grid as an array of rows
+fn rows(w: usize, h: usize, cell: impl Fn(usize, usize) -> Value) -> Value {
+    (0..h)
+        .map(|y| (0..w).map(|x| cell(x, y)).collect::<Value>())
+        .collect()
+}
+
+/// Check that a value is 3 layers of h rows of w cells
+fn layers<'a>(value: Option<&'a Value>, w: usize, h: usize, what: &str) -> Result<Vec<Vec<&'a [Value]>>, String> {
+    let layers = value.and_then(Value::as_array)
+        .filter(|layers| layers.len() == 3)
+        .ok_or_else(|| format!("{what} must have 3 layers"))?;
+
+    layers.iter()
+        .map(|layer| grid(layer, w, h).ok_or_else(|| format!("{what} layers must be {w}x{h}")))
+        .collect()
+}
+
+/// Rows of a w by h grid
+fn grid(value: &Value, w: usize, h: usize) -> Option<Vec<&[Value]>> {
+    let rows = value.as_array().filter(|rows| rows.len() == h)?;
+    rows.iter()
+        .map(|row| row.as_array().filter(|row| row.len() == w).map(Vec::as_slice))
+        .collect()
+}
+
+fn import_geometry_cell(cell: &Value) -> Option<Value> {
+    let geometry = cell.get(0)?.as_u64()?;
+    Geometry::from_data(geometry.try_into().ok()?)?;
+
+    let features = cell.get(1)?.as_array()?;
+    for feature in features {
+        Feature::from_data(feature.as_u64()?.try_into().ok()?)?;
+    }
+
+    Some(json!([geometry, features]))
+}
+
+fn export_tile_cell(cell: &Value) -> Value {
+    let tp = cell.get("#tp").and_then(Value::as_str).unwrap_or("default");
+    let data = cell.get("#Data").cloned().unwrap_or(Value::Null);
+
+    let exported = match tp {
+        "default" => return Value::Null,
+        "material" => data.as_str().map(|material| json!({ "type": "material", "material": material })),
+        "tileHead" => data.get(0)
+            .and_then(Value::as_str)
+            .and_then(parse_index)
+            .zip(data.get(1))
+            .map(|([category, index], name)| json!({ "type": "head", "category": category, "index": index, "name": name })),
+        "tileBody" => data.get(0)
+            .and_then(Value::as_str)
+            .and_then(parse_point)
+            .zip(data.get(1))
+            .map(|(head, layer)| json!({ "type": "body", "head": head, "layer": layer })),
+        _ => None,
+    };
+
+    exported.unwrap_or_else(|| json!({ "type": tp, "data": data }))
+}
+
+fn import_tile_cell(cell: &Value) -> Option<Value> {
+    if cell.is_null() {
+        return Some(json!({ "#tp": "default", "#Data": 0 }))
+    }
+
+    let (tp, data) = match cell.get("type")?.as_str()? {
+        "material" => ("material", cell.get("material")?.clone()),
+        "head" => {
+            let head = format_index(cell.get("category")?, cell.get("index")?)?;
+            ("tileHead", json!([head, cell.get("name")?]))
+        }
+        "body" => {
+            let head = cell.get("head")?;
+            ("tileBody", json!([format_point(head.get(0)?, head.get(1)?)?, cell.get("layer")?]))
+        }
+        other => (other, cell.get("data")?.clone()),
+    };
+
+    Some(json!({ "#tp": tp, "#Data": data }))
+}
+
+/// Effect matrices are stored as [x][y] by the editor, but [y][x] in the schema
+fn import_effect(effect: &Value, w: usize, h: usize) -> Option<Value> {
+    let matrix = grid(effect.get("matrix")?, w, h)?;
+    let mtrx: Vec<Vec<f64>> = (0..w)
+        .map(|x| (0..h).map(|y| matrix[y][x].as_f64()).collect::<Option<Vec<_>>>())
+        .collect::<Option<_>>()?;
+
+    let mut entry = Map::new();
+    entry.insert("#nm".into(), effect.get("name")?.as_str()?.into());
+    for (key, value) in effect.get("extra").and_then(Value::as_object).into_iter().flatten() {
+        entry.insert(format!("#{key}"), add_keys(value));
+    }
+    entry.insert("#mtrx".into(), mtrx.into());
+    entry.insert("#Options".into(), effect.get("options").cloned().unwrap_or_else(|| json!([])));
+
+    Some(entry.into())
+}
+
+/// Props are stored as `[depth, name, point(category, index), [corners], [settings]]`
+fn export_prop(prop: &Value) -> Value {
+    let [category, index] = prop.get(2)
+        .and_then(Value::as_str)
+        .and_then(parse_index)
+        .unwrap_or_default();
+
+    let quad: Vec<[f64; 2]> = prop.get(3)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter_map(parse_point)
+        .collect();
+
+    json!({
+        "depth": prop.get(0),
+        "name": prop.get(1),
+        "category": category,
+        "index": index,
+        "quad": quad,
+        "settings": prop.get(4).map(strip_keys),
+    })
+}
+
+fn import_prop(prop: &Value) -> Option<Value> {
+    let quad: Vec<String> = prop.get("quad")?
+        .as_array()?
+        .iter()
+        .map(|corner| format_point(corner.get(0)?, corner.get(1)?))
+        .collect::<Option<_>>()?;
+
+    Some(json!([
+        prop.get("depth")?,
+        prop.get("name")?,
+        format_index(prop.get("category")?, prop.get("index")?)?,
+        quad,
+        prop.get("settings").map_or_else(|| json!({}), add_keys),
+    ]))
+}
+
+/// Parse a `point(x, y)` which may hold decimals
+fn parse_point(s: &str) -> Option<[f64; 2]> {
+    let (x, y) = s.strip_prefix("point(")?.strip_suffix(')')?.split_once(',')?;
+    Some([x.trim().parse().ok()?, y.trim().parse().ok()?])
+}
+
+/// Parse the `point(category, index)` naming a tile or prop, which must be integers
+fn parse_index(s: &str) -> Option<[i64; 2]> {
+    let [category, index] = parse_point(s)?;
+    (category.fract() == 0.0 && index.fract() == 0.0).then_some([category as i64, index as i64])
+}
+
+/// Inverse of [parse_index], or None if either isn't an integer
+fn format_index(category: &Value, index: &Value) -> Option<String> {
+    Some(format!("point({}, {})", category.as_i64()?, index.as_i64()?))
+}
+
+/// Integers are written as-is, anything else with the 4 decimals Lingo uses
+fn format_point(x: &Value, y: &Value) -> Option<String> {
+    let coord = |v: &Value| match v.as_i64() {
+        Some(i) => Some(i.to_string()),
+        None => v.as_f64().map(|f| format!("{f:.4}")),
+    };
+
+    Some(format!("point({}, {})", coord(x)?, coord(y)?))
+}
+
+fn strip_key(key: &str) -> String {
+    key.strip_prefix('#').unwrap_or(key).to_owned()
+}
+
+/// Remove the leading `#` of every key, at any depth
+fn strip_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => map.iter().map(|(k, v)| (strip_key(k), strip_keys(v))).collect(),
+        Value::Array(arr) => arr.iter().map(strip_keys).collect(),
+        other => other.clone(),
+    }
+}
+
+/// Inverse of [strip_keys]
+fn add_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => map.iter().map(|(k, v)| (format!("#{k}"), add_keys(v))).collect(),
+        Value::Array(arr) => arr.iter().map(add_keys).collect(),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rwlevel::fixture::{self, Edit}, RWLevel};
+
+    #[test]
+    fn test_round_trip() {
+        let project = fixture::project(&[
+            "#E###",
+            "#o|.#",
+            "#>.w#",
+        ])
+            .edit("#tlMatrix: []", r#"#tlMatrix: [[[[#tp: "material", #Data: "Concrete"], [#tp: "default", #Data: 0], [#tp: "default", #Data: 0]]]]"#)
+            .edit("#props: []", r#"#props: [[-5, "Pipe", point(2, 3), [point(10.5000, 20), point(30, 20), point(30, 40), point(10, 40)], [#settings: [#renderorder: 0, #seed: 12]]]]"#)
+            .edit("#effects: []", r#"#effects: [[#nm: "Slime", #tp: "nn", #mtrx: [[1, 2, 3], [4, 5, 6], [7, 8, 9], [0, 0, 0], [0, 0, 0]], #Options: [["Seed", [], 4]]]]"#);
+        let level = RWLevel::from_project_str("TEST", &project).unwrap();
+
+        let exported = level.export_json();
+        assert_eq!(exported["geometry"][0][2][1], serde_json::json!([7, [4]]));
+        assert_eq!(exported["effects"][0]["matrix"][0], serde_json::json!([1.0, 4.0, 7.0, 0.0, 0.0]));
+        assert_eq!(exported["effects"][0]["extra"]["tp"], "nn");
+        assert_eq!(exported["props"][0]["quad"][0], serde_json::json!([10.5, 20.0]));
+        assert_eq!(exported["props"][0]["index"], 3);
+        assert_eq!(exported["props"][0]["settings"]["settings"]["seed"], 12);
+        assert_eq!(exported["tiles"]["layers"][0][0][0]["material"], "Concrete");
+        assert!(exported["tiles"]["layers"][0][0][1].is_null());
+
+        let imported = RWLevel::import_json(&exported).unwrap();
+        assert_eq!(imported.export_json(), exported);
+        assert!(imported.to_project_string().contains(r#""Pipe", point(2, 3)"#));
+        assert_eq!(imported.tile(RWLevel::L1_FG, 1, 2), level.tile(RWLevel::L1_FG, 1, 2));
+
+        let mut bad = exported.clone();
+        bad["geometry"][1][0][0] = serde_json::json!([8, []]);
+        assert!(RWLevel::import_json(&bad).is_err());
+
+        let mut bad = exported.clone();
+        bad["props"][0]["index"] = "3".into();
+        assert!(RWLevel::import_json(&bad).is_err());
+    }
+}
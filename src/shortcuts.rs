@@ -0,0 +1,312 @@
+//! Follows the shortcut path dots on layer 1 of a level to find where each
+//! shortcut entrance leads.
+
+use std::collections::HashSet;
+
+use crate::{Connectivity, Feature, Geometry, RWLevel, Tile};
+
+/// Where a shortcut ends up when followed from its entrance
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShortcutEnd {
+    /// Another shortcut entrance in the same room
+    Entrance((usize, usize)),
+    /// A pipe leading to another room
+    RoomExit((usize, usize)),
+    CreatureDen((usize, usize)),
+    ScavengerHole((usize, usize)),
+    GopherHole((usize, usize)),
+    /// The path stops at this tile without reaching anything
+    Dangling((usize, usize)),
+    /// The path can continue in more than one direction from this tile
+    Branch((usize, usize)),
+    /// The path runs back into a tile it already passed through
+    Loop((usize, usize)),
+}
+
+/// A shortcut as traced from one of its entrances
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shortcut {
+    pub entrance: (usize, usize),
+    /// Path dots in the order they are travelled, excluding the entrance and the end
+    pub path: Vec<(usize, usize)>,
+    pub end: ShortcutEnd,
+}
+
+/// A room exit or den, as numbered by the game
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RoomNode {
+    /// Node index used by the game. For exits this is the position of the
+    /// connection in the room's `ROOMS:` line of the world file
+    pub index: usize,
+    /// Shortcut entrance inside the room
+    pub entrance: (usize, usize),
+    /// Tile the shortcut ends on
+    pub end: (usize, usize),
+}
+
+/// The nodes of a room which the world file and region spawns refer to.
+/// Exits are numbered first, followed by dens
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoomNodes {
+    pub exits: Vec<RoomNode>,
+    pub dens: Vec<RoomNode>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShortcutNetwork {
+    /// One per shortcut entrance, in the order the game scans them:
+    /// by column from the left, then from the bottom of the room upwards
+    pub shortcuts: Vec<Shortcut>,
+    /// Path dots which no shortcut passes through
+    pub orphans: Vec<(usize, usize)>,
+}
+
+impl Tile {
+    /// Either half of a shortcut entrance. The geometry and the feature
+    /// should always appear together, but the editor doesn't enforce it
+    pub fn is_shortcut_entrance(&self) -> bool {
+        self.geometry == Geometry::ShortcutEntrance || self.features().contains(&Feature::ShortcutEntrance)
+    }
+}
+
+impl ShortcutEnd {
+    pub fn is_broken(&self) -> bool {
+        matches!(self, Self::Dangling(_) | Self::Branch(_) | Self::Loop(_))
+    }
+
+    /// The tile the shortcut ended on
+    pub fn pos(&self) -> (usize, usize) {
+        match *self {
+            Self::Entrance(pos)
+            | Self::RoomExit(pos)
+            | Self::CreatureDen(pos)
+            | Self::ScavengerHole(pos)
+            | Self::GopherHole(pos)
+            | Self::Dangling(pos)
+            | Self::Branch(pos)
+            | Self::Loop(pos) => pos,
+        }
+    }
+}
+
+impl ShortcutNetwork {
+    /// Shortcuts which lead somewhere. Shortcuts joining two entrances are
+    /// only listed once, from the entrance which is scanned first
+    pub fn connections(&self) -> impl Iterator<Item = &Shortcut> {
+        self.shortcuts.iter()
+            .enumerate()
+            .filter(|(idx, shortcut)| match shortcut.end {
+                ShortcutEnd::Entrance(other) => !self.shortcuts[..*idx].iter().any(|s| s.entrance == other),
+                end => !end.is_broken(),
+            })
+            .map(|(_, shortcut)| shortcut)
+    }
+
+    /// Shortcuts which dangle, branch or loop
+    pub fn broken(&self) -> impl Iterator<Item = &Shortcut> {
+        self.shortcuts.iter().filter(|shortcut| shortcut.end.is_broken())
+    }
+
+    /// Number the room exits and dens the same way the game does: in scan
+    /// order of their entrances, with all exits before the first den
+    pub fn nodes(&self) -> RoomNodes {
+        let exits: Vec<RoomNode> = self.shortcuts.iter()
+            .filter_map(|shortcut| match shortcut.end {
+                ShortcutEnd::RoomExit(end) => Some((shortcut.entrance, end)),
+                _ => None,
+            })
+            .enumerate()
+            .map(|(index, (entrance, end))| RoomNode { index, entrance, end })
+            .collect();
+
+        let dens = self.shortcuts.iter()
+            .filter_map(|shortcut| match shortcut.end {
+                ShortcutEnd::CreatureDen(end) => Some((shortcut.entrance, end)),
+                _ => None,
+            })
+            .enumerate()
+            .map(|(index, (entrance, end))| RoomNode { index: exits.len() + index, entrance, end })
+            .collect();
+
+        RoomNodes {
+            exits,
+            dens,
+        }
+    }
+}
+
+impl RWLevel {
+    /// Trace every shortcut entrance on layer 1
+    pub fn shortcuts(&self) -> ShortcutNetwork {
+        let (w, h) = self.meta().dimensions();
+        let mut visited = HashSet::new();
+
+        let shortcuts: Vec<Shortcut> = (0..w)
+            .flat_map(|x| (0..h).rev().map(move |y| (x, y)))
+            .filter(|&(x, y)| self.fg(x, y).is_some_and(|tile| tile.is_shortcut_entrance()))
+            .map(|entrance| {
+                let shortcut = self.trace(entrance);
+                visited.extend(shortcut.path.iter().copied());
+                shortcut
+            })
+            .collect();
+
+        let orphans = (0..w)
+            .flat_map(|x| (0..h).rev().map(move |y| (x, y)))
+            .filter(|pos| !visited.contains(pos))
+            .filter(|&(x, y)| self.fg(x, y).is_some_and(|tile| terminal(tile, (x, y)).is_none()
+                && tile.features().contains(&Feature::ShortcutPathDot)))
+            .collect();
+
+        ShortcutNetwork {
+            shortcuts,
+            orphans,
+        }
+    }
+
+    /// Room exits and dens, numbered as the game numbers them
+    pub fn room_nodes(&self) -> RoomNodes {
+        self.shortcuts().nodes()
+    }
+
+    /// Follow the path from a single entrance until it ends
+    fn trace(&self, entrance: (usize, usize)) -> Shortcut {
+        let mut path = Vec::new();
+        let mut seen = HashSet::from([entrance]);
+        let mut prev = None;
+        let mut pos = entrance;
+
+        let end = loop {
+            let next: Vec<(usize, usize)> = self.neighbours(RWLevel::L1_FG, pos.0, pos.1, Connectivity::Four)
+                .filter(|&(x, y, tile)| Some((x, y)) != prev && is_path(tile))
+                .map(|(x, y, _)| (x, y))
+                .collect();
+
+            let &[step] = next.as_slice() else {
+                break if next.is_empty() {
+                    ShortcutEnd::Dangling(pos)
+                } else {
+                    ShortcutEnd::Branch(pos)
+                };
+            };
+
+            if !seen.insert(step) {
+                break ShortcutEnd::Loop(step);
+            }
+
+            if let Some(end) = self.fg(step.0, step.1).and_then(|tile| terminal(tile, step)) {
+                break end;
+            }
+
+            path.push(step);
+            prev = Some(pos);
+            pos = step;
+        };
+
+        Shortcut {
+            entrance,
+            path,
+            end,
+        }
+    }
+
+    fn fg(&self, x: usize, y: usize) -> Option<Tile> {
+        self.tile(RWLevel::L1_FG, x, y)
+    }
+}
+
+/// If a shortcut ends on this tile, what kind of end it is
+fn terminal(tile: Tile, pos: (usize, usize)) -> Option<ShortcutEnd> {
+    if tile.is_shortcut_entrance() {
+        return Some(ShortcutEnd::Entrance(pos))
+    }
+
+    tile.features().iter().find_map(|feature| match feature {
+        Feature::RoomEntrance => Some(ShortcutEnd::RoomExit(pos)),
+        Feature::CreatureDen => Some(ShortcutEnd::CreatureDen(pos)),
+        Feature::ScavengerHole => Some(ShortcutEnd::ScavengerHole(pos)),
+        Feature::GopherHole => Some(ShortcutEnd::GopherHole(pos)),
+        _ => None,
+    })
+}
+
+/// Tiles a shortcut can travel through or end on
+fn is_path(tile: Tile) -> bool {
+    tile.features().contains(&Feature::ShortcutPathDot) || terminal(tile, (0, 0)).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rwlevel::fixture;
+
+    use super::{RoomNode, ShortcutEnd};
+
+    #[test]
+    fn test_destinations() {
+        let level = fixture::level(&[
+            "#E##o#D",
+            "#o##o#o",
+            "#>..>.>",
+            "#######",
+        ]);
+
+        let network = level.shortcuts();
+        let ends: Vec<ShortcutEnd> = network.shortcuts.iter().map(|s| s.end).collect();
+        assert_eq!(ends, [
+            ShortcutEnd::RoomExit((1, 0)),
+            ShortcutEnd::Dangling((4, 0)),
+            ShortcutEnd::CreatureDen((6, 0)),
+        ]);
+        assert_eq!(network.shortcuts[1].path, [(4, 1), (4, 0)]);
+        assert_eq!(network.connections().count(), 2);
+        assert!(network.orphans.is_empty());
+    }
+
+    #[test]
+    fn test_entrance_pair() {
+        let level = fixture::level(&[
+            "#####o#",
+            "#oooo##",
+            "#>##>.#",
+            "#######",
+        ]);
+
+        let network = level.shortcuts();
+        assert_eq!(network.shortcuts.len(), 2);
+        assert_eq!(network.shortcuts[0].end, ShortcutEnd::Entrance((4, 2)));
+        assert_eq!(network.shortcuts[1].end, ShortcutEnd::Entrance((1, 2)));
+        assert_eq!(network.connections().count(), 1);
+        assert_eq!(network.orphans, [(5, 0)]);
+    }
+
+    #[test]
+    fn test_branch() {
+        let level = fixture::level(&[
+            "#ooo#",
+            "##o##",
+            "##>##",
+        ]);
+
+        let network = level.shortcuts();
+        assert_eq!(network.shortcuts[0].end, ShortcutEnd::Branch((2, 0)));
+        assert_eq!(network.broken().count(), 1);
+    }
+
+    #[test]
+    fn test_node_numbering() {
+        let level = fixture::level(&[
+            "######",
+            "E>####",
+            "######",
+            "E>##>D",
+            "######",
+        ]);
+
+        let nodes = level.room_nodes();
+        assert_eq!(nodes.exits, [
+            RoomNode { index: 0, entrance: (1, 3), end: (0, 3) },
+            RoomNode { index: 1, entrance: (1, 1), end: (0, 1) },
+        ]);
+        assert_eq!(nodes.dens, [RoomNode { index: 2, entrance: (4, 3), end: (5, 3) }]);
+    }
+}
@@ -1,6 +1,15 @@
+mod layer;
+
+use std::{fmt, ops::{Deref, DerefMut}};
+
+use serde_json::{json, Value};
+
+pub use layer::TileLayer;
+
 #[allow(unused)]
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Geometry {
     /// Passable tile
     Air = 0,
@@ -25,6 +34,7 @@ pub enum Geometry {
 #[allow(unused)]
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Feature {
     /// Horizontal pole that can be climbed on
     HPole = 1,
@@ -60,37 +70,216 @@ pub enum Feature {
     ScavengerHole = 21,
 }
 
-#[allow(unused)]
+/// A set of features, one bit per feature code. Every code fits in 32 bits
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FeatureSet(u32);
+
+impl FeatureSet {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// The set with `feature` added, for building sets in consts
+    pub const fn with(self, feature: Feature) -> Self {
+        Self(self.0 | 1 << feature as u8)
+    }
+
+    /// Add a feature, returning whether it wasn't already present
+    pub fn insert(&mut self, feature: Feature) -> bool {
+        let had = self.contains(&feature);
+        self.0 |= 1 << feature as u8;
+        !had
+    }
+
+    /// Remove a feature, returning whether it was present
+    pub fn remove(&mut self, feature: &Feature) -> bool {
+        let had = self.contains(feature);
+        self.0 &= !(1 << *feature as u8);
+        had
+    }
+
+    pub fn contains(&self, feature: &Feature) -> bool {
+        self.0 & (1 << *feature as u8) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Features in the order of their codes
+    pub fn iter(&self) -> FeatureIter {
+        FeatureIter(self.0)
+    }
+
+    /// Features in the order of their codes, as a list
+    pub fn to_vec(&self) -> Vec<Feature> {
+        self.iter().collect()
+    }
+
+    /// The raw bitset, bit N set for feature code N
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// A set from the raw bitset, or None if a bit isn't a feature code
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        // Codes 1-7, 9-13 and 18-21
+        const FEATURES: u32 = 0x003c_3efe;
+        (bits & !FEATURES == 0).then_some(Self(bits))
+    }
+}
+
+impl fmt::Debug for FeatureSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<Feature> for FeatureSet {
+    fn from_iter<I: IntoIterator<Item = Feature>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Feature> for FeatureSet {
+    fn extend<I: IntoIterator<Item = Feature>>(&mut self, iter: I) {
+        for feature in iter {
+            self.insert(feature);
+        }
+    }
+}
+
+impl<const N: usize> From<[Feature; N]> for FeatureSet {
+    fn from(features: [Feature; N]) -> Self {
+        features.into_iter().collect()
+    }
+}
+
+impl IntoIterator for FeatureSet {
+    type Item = Feature;
+    type IntoIter = FeatureIter;
+
+    fn into_iter(self) -> FeatureIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &FeatureSet {
+    type Item = Feature;
+    type IntoIter = FeatureIter;
+
+    fn into_iter(self) -> FeatureIter {
+        self.iter()
+    }
+}
+
+/// Iterator over a [`FeatureSet`], lowest code first
 #[derive(Clone, Debug)]
+pub struct FeatureIter(u32);
+
+impl Iterator for FeatureIter {
+    type Item = Feature;
+
+    fn next(&mut self) -> Option<Feature> {
+        while self.0 != 0 {
+            let code = self.0.trailing_zeros() as u8;
+            self.0 &= self.0 - 1;
+            if let Some(feature) = Feature::from_data(code) {
+                return Some(feature)
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.0.count_ones() as usize))
+    }
+}
+
+/// A tile of a layer. Since 0.2 the features are a [FeatureSet] rather than a
+/// `Vec<Feature>`, so they read in code order without repeats. The order the
+/// project lists them in is kept by the layer, see [TileLayer::listed_features]
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tile {
     pub geometry: Geometry,
-    pub features: Vec<Feature>,
+    pub features: FeatureSet,
 }
 
 impl Default for Tile {
     fn default() -> Self {
         Self {
             geometry: Geometry::Wall,
-            features: vec![],
+            features: FeatureSet::new(),
         }
     }
 }
 
 impl Tile {
     pub fn add_features(&mut self, features: &[Feature]) {
+        self.features.extend(features.iter().copied());
+    }
+
+    pub fn remove_features(&mut self, features: &[Feature]) {
         for feature in features {
-            if !self.features.contains(&feature) {
-                self.features.push(*feature);
-            }
+            self.features.remove(feature);
         }
     }
 
-    pub fn remove_features(&mut self, features: &[Feature]) {
-        self.features.retain(|f| !features.contains(f));
+    /// The features of the tile. Before 0.2 this was a `&[Feature]`, use
+    /// [FeatureSet::to_vec] where a list is still needed
+    pub fn features(&self) -> FeatureSet {
+        self.features
+    }
+
+    /// The tile as the editor stores it, `[geometry, [features]]`
+    pub(crate) fn to_json(self) -> Value {
+        let features: Vec<u8> = self.features.iter().map(|f| f as u8).collect();
+        json!([self.geometry as u8, features])
     }
+}
+
+/// A tile of a level being edited in place, see [`RWLevel::tile_mut`](crate::RWLevel::tile_mut).
+/// Dereferences to a copy of the [`Tile`], which is written back to the level when dropped
+#[derive(Debug)]
+pub struct TileMut<'a> {
+    tile: Tile,
+    /// Where the geometry and features of the tile are stored in its layer
+    cells: (&'a mut Geometry, &'a mut FeatureSet),
+}
 
-    pub fn features(&self) -> &[Feature] {
-        &self.features
+impl<'a> TileMut<'a> {
+    pub(crate) fn new(geometry: &'a mut Geometry, features: &'a mut FeatureSet) -> Self {
+        Self { tile: Tile { geometry: *geometry, features: *features }, cells: (geometry, features) }
+    }
+}
+
+impl Deref for TileMut<'_> {
+    type Target = Tile;
+
+    fn deref(&self) -> &Tile {
+        &self.tile
+    }
+}
+
+impl DerefMut for TileMut<'_> {
+    fn deref_mut(&mut self) -> &mut Tile {
+        &mut self.tile
+    }
+}
+
+impl Drop for TileMut<'_> {
+    fn drop(&mut self) {
+        *self.cells.0 = self.tile.geometry;
+        *self.cells.1 = self.tile.features;
     }
 }
 
@@ -98,10 +287,15 @@ impl Geometry {
     pub fn to_tile(&self) -> Tile {
         Tile {
             geometry: *self,
-            features: vec![],
+            features: FeatureSet::new(),
         }
     }
 
+    /// Whether the whole tile blocks movement, like a wall
+    pub fn is_solid(self) -> bool {
+        matches!(self, Self::Wall | Self::Glass)
+    }
+
     pub fn from_data(data: u8) -> Option<Self> {
         Some(match data {
             0 => Self::Air,
@@ -140,4 +334,56 @@ impl Feature {
             _ => return None,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{rwlevel::fixture::{self, Edit}, Geometry, RWLevel};
+
+    use super::{Feature, FeatureSet};
+
+    #[test]
+    fn test_feature_set() {
+        let mut set: FeatureSet = [Feature::ScavengerHole, Feature::HPole, Feature::Rock].into();
+        assert!(!set.insert(Feature::HPole));
+        assert!(set.remove(&Feature::Rock));
+        assert!(!set.remove(&Feature::Rock));
+
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&Feature::ScavengerHole));
+        assert_eq!(set.to_vec(), [Feature::HPole, Feature::ScavengerHole]);
+        assert_eq!(format!("{set:?}"), "[HPole, ScavengerHole]");
+    }
+
+    #[test]
+    fn test_tile_mut() {
+        let mut level = fixture::level(&["#|#"]);
+
+        // Edits through the guard land in the level once it's dropped
+        let mut tile = level.tile_mut(RWLevel::L1_FG, 1, 0).unwrap();
+        tile.geometry = Geometry::Floor;
+        tile.add_features(&[Feature::HPole]);
+        drop(tile);
+
+        let tile = level.tile(RWLevel::L1_FG, 1, 0).unwrap();
+        assert_eq!(tile.geometry, Geometry::Floor);
+        assert_eq!(tile.features().to_vec(), [Feature::HPole, Feature::VPole]);
+    }
+
+    #[test]
+    fn test_listed_features() {
+        let project = fixture::project(&["#+#"]).edit("[[0, [1, 2]]", "[[0, [2, 1, 2]]");
+        let mut level = RWLevel::from_project_str("TEST", &project).unwrap();
+        let listed = |level: &RWLevel| level.layer(RWLevel::L1_FG).unwrap().listed_features(1, 0).unwrap();
+
+        assert_eq!(level.tile(RWLevel::L1_FG, 1, 0).unwrap().features().to_vec(), [Feature::HPole, Feature::VPole]);
+        assert_eq!(listed(&level), [Feature::VPole, Feature::HPole, Feature::VPole]);
+
+        // Added features follow the listed ones, removed ones drop out
+        let mut tile = level.tile_mut(RWLevel::L1_FG, 1, 0).unwrap();
+        tile.add_features(&[Feature::Hive]);
+        tile.remove_features(&[Feature::HPole]);
+        drop(tile);
+        assert_eq!(listed(&level), [Feature::VPole, Feature::VPole, Feature::Hive]);
+    }
+}
@@ -0,0 +1,131 @@
+//! One layer of a level's tiles, stored as a grid of geometry and a grid of
+//! feature sets rather than a grid of whole tiles.
+
+use serde_json::{json, Value};
+
+use crate::grid::Grid;
+
+use super::{Feature, FeatureSet, Geometry, Tile, TileMut};
+
+/// The tiles of a layer, indexed by `(x, y)` like a [`Grid`]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileLayer {
+    geometry: Grid<Geometry>,
+    features: Grid<FeatureSet>,
+    /// Features of the tiles which listed them out of code order or more than
+    /// once in the project, as listed there, so saving doesn't reorder them.
+    /// Sorted by (x, y)
+    listed: Vec<((usize, usize), Vec<Feature>)>,
+}
+
+impl TileLayer {
+    /// A layer of solid wall
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            geometry: Grid::filled(width, height, Geometry::Wall),
+            features: Grid::new(width, height),
+            listed: vec![],
+        }
+    }
+
+    /// (Width, Height)
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.geometry.dimensions()
+    }
+
+    /// The tile at (x, y), or None if out of bounds
+    pub fn get(&self, x: usize, y: usize) -> Option<Tile> {
+        Some(Tile {
+            geometry: *self.geometry.get(x, y)?,
+            features: *self.features.get(x, y)?,
+        })
+    }
+
+    /// The tile at (x, y) for editing, or None if out of bounds
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<TileMut<'_>> {
+        let geometry = self.geometry.get_mut(x, y)?;
+        let features = self.features.get_mut(x, y)?;
+        Some(TileMut::new(geometry, features))
+    }
+
+    /// Every tile as `(x, y, tile)`, row by row
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, Tile)> + '_ {
+        self.geometry.iter()
+            .zip(self.features.iter())
+            .map(|((x, y, &geometry), (_, _, &features))| (x, y, Tile { geometry, features }))
+    }
+
+    /// The geometry of every tile
+    pub fn geometry(&self) -> &Grid<Geometry> {
+        &self.geometry
+    }
+
+    /// The features of every tile
+    pub fn features(&self) -> &Grid<FeatureSet> {
+        &self.features
+    }
+
+    /// Change the dimensions, keeping the top-left corner and filling new tiles with `tile`
+    pub(crate) fn resize(&mut self, width: usize, height: usize, tile: Tile) {
+        self.geometry.resize(width, height, tile.geometry);
+        self.features.resize(width, height, tile.features);
+        self.listed.retain(|&((x, y), _)| x < width && y < height);
+    }
+
+    /// Remember the order the project listed the features of (x, y) in
+    pub(crate) fn set_listed(&mut self, (x, y): (usize, usize), features: Vec<Feature>) {
+        match self.listed.binary_search_by_key(&(x, y), |(pos, _)| *pos) {
+            Ok(idx) => self.listed[idx].1 = features,
+            Err(idx) => self.listed.insert(idx, ((x, y), features)),
+        }
+    }
+
+    /// The features of (x, y) as the project listed them, if not in code order
+    fn listed(&self, x: usize, y: usize) -> Option<&[Feature]> {
+        self.listed.binary_search_by_key(&(x, y), |(pos, _)| *pos)
+            .ok()
+            .map(|idx| self.listed[idx].1.as_slice())
+    }
+
+    /// Every tile with its features as the project listed them, as `((x, y), features)`
+    pub(crate) fn all_listed(&self) -> &[((usize, usize), Vec<Feature>)] {
+        &self.listed
+    }
+
+    /// (X, Y) of the tiles which listed a feature more than once
+    pub(crate) fn duplicated(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.listed.iter()
+            .filter(|(_, features)| features.iter().copied().collect::<FeatureSet>().len() != features.len())
+            .map(|(pos, _)| *pos)
+    }
+
+    /// Drop the repeats of features listed more than once, keeping the first of each
+    pub(crate) fn dedup_listed(&mut self) {
+        for (_, features) in &mut self.listed {
+            let mut seen = FeatureSet::new();
+            features.retain(|&feature| seen.insert(feature));
+        }
+    }
+
+    /// The features of (x, y) in the order the project listed them when loaded,
+    /// repeats included, followed by any added since in code order.
+    /// None if out of bounds
+    pub fn listed_features(&self, x: usize, y: usize) -> Option<Vec<Feature>> {
+        let features = *self.features.get(x, y)?;
+        let Some(listed) = self.listed(x, y) else {
+            return Some(features.to_vec())
+        };
+
+        let kept = listed.iter().copied().filter(|feature| features.contains(feature));
+        let added = features.iter().filter(|feature| !listed.contains(feature));
+        Some(kept.chain(added).collect())
+    }
+
+    /// The tile at (x, y) as the editor stores it, `[geometry, [features]]`,
+    /// with the features as in [TileLayer::listed_features]
+    pub(crate) fn tile_json(&self, x: usize, y: usize) -> Option<Value> {
+        let codes: Vec<u8> = self.listed_features(x, y)?.into_iter().map(|feature| feature as u8).collect();
+        Some(json!([*self.geometry.get(x, y)? as u8, codes]))
+    }
+}